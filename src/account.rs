@@ -0,0 +1,225 @@
+//! Registered players. Accounts are kept in a plain text file with one line
+//! per account:
+//!
+//! ```text
+//! Kekse 100000 5F0C3B29A1D4E6F70819A2B3C4D5E6F7 9A3C...
+//! ```
+//!
+//! holding the name, the number of PBKDF2-HMAC-SHA256 rounds, the salt and
+//! the derived key, both hex encoded. Passwords themselves are never stored.
+
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
+use pbkdf2::pbkdf2_hmac;
+use rand::{OsRng, Rng};
+use sha2::Sha256;
+use network::*;
+
+// TODO import
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Rounds of key derivation for new passwords.
+const ROUNDS: u32 = 100_000;
+
+const SALT_LENGTH: usize = 16;
+
+const KEY_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub name: String,
+    rounds: u32,
+    salt: Vec<u8>,
+    key: Vec<u8>,
+}
+
+/// All registered accounts, saved to `path` after every change if one is
+/// given.
+#[derive(Debug, Clone, Default)]
+pub struct Accounts {
+    path: Option<PathBuf>,
+    accounts: HashMap<String, Account>,
+    /// Names nobody may use without logging in, even if not registered.
+    reserved: HashSet<String>,
+    /// Name and password of the server admin, who logs in without an
+    /// account.
+    admin: Option<(String, String)>,
+}
+
+impl Account {
+    fn new(name: &str, password: &str) -> Result<Account> {
+        let mut salt = vec![0; SALT_LENGTH];
+        OsRng::new()?.fill_bytes(&mut salt);
+        let key = derive_key(password, &salt, ROUNDS);
+        Ok(Account {
+            name: name.into(),
+            rounds: ROUNDS,
+            salt,
+            key,
+        })
+    }
+
+    pub fn verify(&self, password: &str) -> bool {
+        let key = derive_key(password, &self.salt, self.rounds);
+        constant_eq(&key, &self.key)
+    }
+
+    fn parse(line: &str) -> Result<Account> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        if parts.len() != 4 {
+            return Err(durak_error!(InvalidConfig, "Invalid account line."));
+        }
+        Ok(Account {
+            name: parts[0].into(),
+            rounds: parts[1].parse()?,
+            salt: from_hex(parts[2])?,
+            key: from_hex(parts[3])?,
+        })
+    }
+}
+
+impl Accounts {
+    /// Accounts kept in memory only.
+    pub fn new() -> Accounts {
+        Accounts::default()
+    }
+
+    /// Loads the accounts stored at `path`. A missing file is created with
+    /// the first registration.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Accounts> {
+        let path = path.into();
+        let mut accounts = HashMap::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let account = Account::parse(&line)?;
+                accounts.insert(account.name.clone(), account);
+            }
+        }
+        Ok(Accounts {
+            path: Some(path),
+            accounts,
+            reserved: HashSet::new(),
+            admin: None,
+        })
+    }
+
+    pub fn reserve<S: Into<String>>(&mut self, name: S) {
+        self.reserved.insert(name.into().to_lowercase());
+    }
+
+    /// Lets `name` log in with `password` as server admin. The name is
+    /// reserved.
+    pub fn set_admin<S: Into<String>>(&mut self, name: S, password: S) {
+        let name = name.into();
+        self.reserve(name.clone());
+        self.admin = Some((name, password.into()));
+    }
+
+    pub fn is_admin(&self, name: &str) -> bool {
+        self.admin.as_ref().is_some_and(|x| x.0 == name)
+    }
+
+    /// Whether only the owner of an account may use `name`. Names differing
+    /// only in case count as the same.
+    pub fn is_reserved(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.reserved.contains(&name) || self.accounts.keys().any(|x| x.to_lowercase() == name)
+    }
+
+    pub fn register(&mut self, name: &str, password: &str) -> Result<()> {
+        if self.is_reserved(name) {
+            return Err(durak_error!(NameReserved, "Name already taken."));
+        }
+        if name.is_empty() || name.chars().any(char::is_whitespace) {
+            return Err(durak_error!(InvalidArgument, "Account names are single words."));
+        }
+        if password.is_empty() {
+            return Err(durak_error!(MissingArgument, "No password specified."));
+        }
+        self.accounts.insert(name.into(), Account::new(name, password)?);
+        if let Err(e) = self.save() {
+            self.accounts.remove(name);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.accounts.contains_key(name)
+    }
+
+    /// Checks the password of the account `name` or of the admin. Other
+    /// reserved names cannot be logged in to.
+    pub fn verify(&self, name: &str, password: &str) -> bool {
+        if let Some((ref admin, ref secret)) = self.admin {
+            if admin == name {
+                return constant_eq(secret.as_bytes(), password.as_bytes());
+            }
+        }
+        self.accounts.get(name).is_some_and(|x| x.verify(password))
+    }
+
+    /// Writes all accounts to a temporary file that replaces the old one, so
+    /// that a crash never leaves a half written file behind.
+    fn save(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let temporary = path.with_extension("tmp");
+        {
+            let mut file = File::create(&temporary)?;
+            let mut names: Vec<&String> = self.accounts.keys().collect();
+            names.sort();
+            for name in names {
+                let account = &self.accounts[name];
+                writeln!(
+                    file,
+                    "{} {} {} {}",
+                    account.name,
+                    account.rounds,
+                    to_hex(&account.salt),
+                    to_hex(&account.key)
+                )?;
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+fn derive_key(password: &str, salt: &[u8], rounds: u32) -> Vec<u8> {
+    let mut key = vec![0; KEY_LENGTH];
+    pbkdf2_hmac::<Sha256>(password.as_bytes(), salt, rounds, &mut key);
+    key
+}
+
+/// Compares in constant time, so that the time taken tells nothing about
+/// where the inputs differ.
+fn constant_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|x| format!("{:02X}", x)).collect()
+}
+
+fn from_hex(s: &str) -> Result<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return Err(durak_error!(InvalidConfig, "Invalid hex string."));
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| Ok(u8::from_str_radix(&s[i..i + 2], 16)?))
+        .collect()
+}
@@ -0,0 +1,553 @@
+extern crate durak;
+extern crate getopts;
+extern crate termion;
+
+use std::collections::HashMap;
+use std::env;
+use std::io::prelude::*;
+use std::io::{self, Stdout};
+use std::process;
+use std::thread;
+use std::time::Duration;
+
+use durak::bot::*;
+use durak::client::*;
+use durak::game::*;
+use durak::i18n::*;
+use durak::leaderboard::*;
+use durak::network::*;
+use getopts::Options;
+use termion::event::Key;
+use termion::input::TermRead;
+use termion::raw::{IntoRawMode, RawTerminal};
+use termion::screen::AlternateScreen;
+use termion::{clear, color, cursor, style};
+
+type Screen = AlternateScreen<RawTerminal<Stdout>>;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Input {
+    Chat,
+    NewTable,
+    Password(TableHash),
+}
+
+struct App {
+    client: DurakClient,
+    name: String,
+    /// Password of the account to log in to or register.
+    password: Option<String>,
+    register: bool,
+    /// Language of the server's messages, also used for events.
+    lang: Language,
+    me: Option<ClientHash>,
+    table: Option<TableHash>,
+    tables: Vec<TableInfo>,
+    players: HashMap<ClientHash, String>,
+    view: Option<GameView>,
+    chat: Vec<String>,
+    status: String,
+    /// Whether we declared to be ready for the next game.
+    ready: bool,
+    selected_table: usize,
+    selected_card: usize,
+    selected_stack: usize,
+    input: Option<(Input, String)>,
+    quit: bool,
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let mut opts = Options::new();
+    opts.optopt("a", "address", "server address (default localhost:2342)", "ADDRESS");
+    opts.optopt("u", "unix", "connect to a Unix domain socket", "PATH");
+    opts.optopt("n", "name", "player name", "NAME");
+    opts.optflag("", "register", "create an account, password from DURAK_PASSWORD");
+    opts.optopt("l", "lang", "language of the messages: en, de or ru", "CODE");
+    opts.optflag("h", "help", "print this help");
+    let matches = match opts.parse(&args[1..]) {
+        Ok(matches) => matches,
+        Err(e) => {
+            eprintln!("ERROR {}", e);
+            process::exit(1);
+        }
+    };
+    if matches.opt_present("help") {
+        print!("{}", opts.usage(&format!("Usage: {} [options]", args[0])));
+        return;
+    }
+    let client = match matches.opt_str("unix") {
+        Some(path) => DurakClient::connect_unix(path),
+        None => {
+            DurakClient::connect(matches.opt_str("address").unwrap_or_else(
+                || "localhost:2342".into(),
+            ))
+        }
+    };
+    let client = match client {
+        Ok(client) => client,
+        Err(e) => {
+            eprintln!("ERROR {}", e);
+            process::exit(1);
+        }
+    };
+    let name = matches.opt_str("name").or_else(|| env::var("USER").ok()).unwrap_or_else(
+        || "player".into(),
+    );
+    let mut app = App::new(client, name);
+    // Kept out of the arguments, which other users can see.
+    app.password = env::var("DURAK_PASSWORD").ok();
+    app.register = matches.opt_present("register");
+    if app.register && app.password.is_none() {
+        eprintln!("ERROR DURAK_PASSWORD is not set.");
+        process::exit(1);
+    }
+    if let Some(lang) = matches.opt_str("lang") {
+        app.lang = match lang.parse() {
+            Ok(lang) => lang,
+            Err(e) => {
+                eprintln!("ERROR {}", e);
+                process::exit(1);
+            }
+        };
+    }
+    if let Err(e) = run(app) {
+        eprintln!("ERROR {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(mut app: App) -> Result<()> {
+    let mut screen = AlternateScreen::from(io::stdout().into_raw_mode()?);
+    let mut keys = termion::async_stdin().keys();
+    match app.password.take() {
+        Some(password) if app.register => app.client.register(app.name.clone(), password)?,
+        Some(password) => app.client.login(app.name.clone(), password)?,
+        None => app.client.set_name(app.name.clone())?,
+    }
+    if app.lang != Language::English {
+        app.client.send_player(PlayerCommand::Lang(app.lang))?;
+    }
+    app.refresh()?;
+    while !app.quit {
+        while let Ok(event) = app.client.events().try_recv() {
+            app.handle_event(event);
+        }
+        while let Some(Ok(key)) = keys.next() {
+            app.handle_key(key)?;
+        }
+        app.draw(&mut screen)?;
+        thread::sleep(Duration::from_millis(50));
+    }
+    write!(screen, "{}", cursor::Show)?;
+    Ok(())
+}
+
+impl App {
+    fn new(client: DurakClient, name: String) -> App {
+        App {
+            client,
+            name,
+            password: None,
+            register: false,
+            lang: Language::English,
+            me: None,
+            table: None,
+            tables: Vec::new(),
+            players: HashMap::new(),
+            view: None,
+            chat: Vec::new(),
+            status: "r refresh  n new table  enter join  w watch  c chat  q quit".into(),
+            ready: false,
+            selected_table: 0,
+            selected_card: 0,
+            selected_stack: 0,
+            input: None,
+            quit: false,
+        }
+    }
+
+    fn refresh(&mut self) -> Result<()> {
+        self.client.send_player(PlayerCommand::State)?;
+        self.client.send_player(PlayerCommand::List)?;
+        self.client.send_table(TableCommand::List)
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::PlayerList(players) => {
+                self.players = players.into_iter().map(|x| (x.hash, x.name)).collect();
+            }
+            Event::PlayerState(player) => {
+                self.me = Some(player.hash);
+                self.lang = player.lang;
+                let table = player.table.or(player.watching);
+                if table != self.table {
+                    self.view = None;
+                }
+                self.table = table;
+            }
+            Event::TableList(tables) => {
+                self.tables = tables;
+                self.selected_table = clamp(self.selected_table, self.tables.len());
+            }
+            Event::Error(error) => self.status = format!("{} ({})", error, error.code()),
+            Event::TableSettings(settings) => {
+                let owner = settings.owner.map(|x| self.player_name(x)).unwrap_or_default();
+                self.status = format!(
+                    "{}: owner {}  players {}-{}  locked {}  spectators {}",
+                    settings.name,
+                    owner,
+                    settings.min_players,
+                    settings.max_players,
+                    if settings.locked { "yes" } else { "no" },
+                    if settings.spectating { "on" } else { "off" }
+                );
+                if let Some(invite) = settings.invite {
+                    self.status = format!("{}  invite {}", self.status, invite);
+                }
+                self.status = format!("{}  abandon {}", self.status, settings.abandon_policy);
+                for (phase, seconds) in settings.timers {
+                    self.status = format!("{}  {} {}s", self.status, phase, seconds);
+                }
+            }
+            Event::MatchSummary(summary) => {
+                let limit = match (summary.games, summary.score) {
+                    (Some(games), _) => format!("{}/{} games", summary.played, games),
+                    (None, Some(score)) => {
+                        format!("{} games, durak {}x ends", summary.played, score)
+                    }
+                    (None, None) => format!("{} games", summary.played),
+                };
+                let standings: Vec<String> = summary
+                    .standings
+                    .iter()
+                    .map(|&(player, count)| format!("{} {}", self.player_name(player), count))
+                    .collect();
+                self.status = format!(
+                    "match{} {}: {}",
+                    if summary.over { " over" } else { "" },
+                    limit,
+                    standings.join(", ")
+                );
+            }
+            Event::PlayerStats(stats) => {
+                self.status = format!(
+                    "{}: rating {:.0}  {} games  durak {}  won {:.0}%  place {:.2}  abandoned {}",
+                    stats.name,
+                    stats.rating,
+                    stats.games,
+                    stats.durak,
+                    stats.win_rate * 100.0,
+                    stats.average_place,
+                    stats.abandoned
+                );
+            }
+            Event::Leaderboard(leaderboard) => {
+                let rating: Vec<String> = leaderboard
+                    .rating
+                    .iter()
+                    .map(|&(ref name, rating)| format!("{} {:.0}", name, rating))
+                    .collect();
+                self.status = format!("{} best: {}", leaderboard.period, rating.join(", "));
+            }
+            Event::MoveTimer(seconds, players) => {
+                let names: Vec<String> = players.iter().map(|x| self.player_name(*x)).collect();
+                let args = [names.join(", "), seconds.to_string()];
+                self.status = describe(self.lang, EventMessage::MoveTimer, &args);
+            }
+            Event::Renamed(player, name) => {
+                let old = self.player_name(player);
+                let text = describe(self.lang, EventMessage::Renamed, &[old, name.clone()]);
+                self.chat.push(text);
+                self.players.insert(player, name);
+            }
+            Event::Chat(sender, message) => {
+                let name = self.player_name(sender);
+                self.chat.push(format!("{}: {}", name, message));
+            }
+            Event::Ready(players) => {
+                self.ready = self.me.is_some_and(|x| players.contains(&x));
+                let names: Vec<String> = players.iter().map(|x| self.player_name(*x)).collect();
+                self.status = format!("ready: {}", names.join(", "));
+            }
+            Event::Countdown(Some(seconds)) => {
+                let args = [seconds.to_string()];
+                self.status = describe(self.lang, EventMessage::Countdown, &args);
+            }
+            Event::Countdown(None) => {
+                self.status = describe(self.lang, EventMessage::CountdownOff, &[]);
+            }
+            Event::Kicked(table) => {
+                if self.table == Some(table) {
+                    self.table = None;
+                    self.view = None;
+                }
+                self.status = describe(self.lang, EventMessage::Kicked, &[]);
+            }
+            Event::GameState(view) => {
+                self.ready = false;
+                self.selected_card = clamp(self.selected_card, view.cards.len());
+                self.selected_stack = clamp(self.selected_stack, view.table_stacks.len());
+                self.view = Some(view);
+            }
+        }
+    }
+
+    fn handle_key(&mut self, key: Key) -> Result<()> {
+        if let Some((kind, mut text)) = self.input.take() {
+            match key {
+                Key::Char('\n') => {
+                    match kind {
+                        Input::Chat => self.client.chat(text)?,
+                        Input::NewTable => {
+                            match TableCommand::parse(format!("new {}", text)) {
+                                Ok(command) => self.client.send_table(command)?,
+                                Err(e) => self.status = e.to_string(),
+                            }
+                            self.refresh()?;
+                        }
+                        Input::Password(table) => {
+                            self.client.join_with(table, text)?;
+                            self.refresh()?;
+                        }
+                    }
+                }
+                Key::Esc => {}
+                Key::Backspace => {
+                    text.pop();
+                    self.input = Some((kind, text));
+                }
+                Key::Char(c) => {
+                    text.push(c);
+                    self.input = Some((kind, text));
+                }
+                _ => self.input = Some((kind, text)),
+            }
+            return Ok(());
+        }
+        let cards = self.hand();
+        match key {
+            Key::Char('q') | Key::Ctrl('c') => self.quit = true,
+            Key::Char('r') => self.refresh()?,
+            Key::Char('c') => self.input = Some((Input::Chat, String::new())),
+            Key::Char('n') => self.input = Some((Input::NewTable, String::new())),
+            Key::Char('s') => self.client.send_game(GameCommand::Start)?,
+            Key::Char('y') => self.client.ready(!self.ready)?,
+            Key::Char('m') => self.client.send_table(TableCommand::Match(None))?,
+            Key::Char('v') => self.client.stats(None)?,
+            Key::Char('o') => self.client.leaderboard(None, Period::AllTime)?,
+            Key::Char('g') => self.client.send_game(GameCommand::State)?,
+            Key::Char('i') => self.client.send_table(TableCommand::Settings(None))?,
+            Key::Char('l') => {
+                self.client.send_table(TableCommand::Leave)?;
+                self.refresh()?;
+            }
+            Key::Up => {
+                if self.table.is_none() {
+                    self.selected_table = self.selected_table.saturating_sub(1);
+                } else {
+                    self.selected_stack = self.selected_stack.saturating_sub(1);
+                }
+            }
+            Key::Down => {
+                if self.table.is_none() {
+                    self.selected_table = clamp(self.selected_table + 1, self.tables.len());
+                } else {
+                    let stacks = self.view.as_ref().map(|x| x.table_stacks.len()).unwrap_or(0);
+                    self.selected_stack = clamp(self.selected_stack + 1, stacks);
+                }
+            }
+            Key::Left => self.selected_card = self.selected_card.saturating_sub(1),
+            Key::Right => self.selected_card = clamp(self.selected_card + 1, cards.len()),
+            Key::Char('\n') | Key::Char('a') => {
+                if self.table.is_none() {
+                    if let Some(table) = self.tables.get(self.selected_table) {
+                        let hash = table.hash;
+                        if table.access == "open" {
+                            self.client.join(hash)?;
+                            self.refresh()?;
+                        } else {
+                            self.input = Some((Input::Password(hash), String::new()));
+                        }
+                    }
+                } else if let Some(card) = cards.get(self.selected_card) {
+                    self.client.put(card.clone(), None)?;
+                }
+            }
+            Key::Char('w') => {
+                if let Some(table) = self.tables.get(self.selected_table) {
+                    if self.table.is_none() {
+                        let hash = table.hash;
+                        self.client.watch(hash)?;
+                        self.refresh()?;
+                    }
+                }
+            }
+            Key::Char('t') => self.client.take()?,
+            Key::Char('p') => self.client.pass()?,
+            Key::Char('b') => self.client.send_table(TableCommand::AddBot(BotLevel::default()))?,
+            Key::Char('d') => {
+                if let Some(card) = cards.get(self.selected_card) {
+                    self.client.put(card.clone(), Some(self.selected_stack))?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Own cards, sorted by suite and value so the selection is stable.
+    fn hand(&self) -> Vec<Card> {
+        let mut cards = self.view.as_ref().map(|x| x.cards.clone()).unwrap_or_default();
+        cards.sort_by_key(|x| (format!("{}", x.suite), x.value.clone() as usize));
+        cards
+    }
+
+    fn player_name(&self, hash: ClientHash) -> String {
+        match self.players.get(&hash) {
+            Some(name) => name.clone(),
+            None => format!("{:016X}", hash),
+        }
+    }
+
+    fn draw(&self, screen: &mut Screen) -> Result<()> {
+        let (width, height) = termion::terminal_size()?;
+        let split = width * 3 / 5;
+        write!(screen, "{}{}", clear::All, cursor::Hide)?;
+        let title = match self.table.and_then(|x| self.tables.iter().find(|t| t.hash == x)) {
+            Some(table) => format!(" durak - {} at {}", self.name, table.name),
+            None => format!(" durak - {} in the lobby", self.name),
+        };
+        write!(screen, "{}{}{}{}", cursor::Goto(1, 1), style::Bold, title, style::Reset)?;
+
+        let mut lines = Vec::new();
+        match (self.table, &self.view) {
+            (None, _) => {
+                lines.push("Tables".to_string());
+                for (i, table) in self.tables.iter().enumerate() {
+                    lines.push(format!(
+                        "{} {}{} ({}/{}) {}",
+                        if i == self.selected_table { ">" } else { " " },
+                        if table.access == "open" { "" } else { "* " },
+                        table.name,
+                        table.players,
+                        table.max_players,
+                        table.state
+                    ));
+                }
+            }
+            (Some(_), None) => {
+                lines.push(
+                    "Waiting for the game, press s to start, y when ready or b for a bot.".into(),
+                );
+            }
+            (Some(_), Some(view)) => {
+                let trump = match (&view.trump_card, &view.trump) {
+                    (Some(card), _) => render_card(card),
+                    (None, Some(suite)) => format!("{:?}", suite),
+                    _ => "-".into(),
+                };
+                lines.push(format!("Trump {}   talon {}", trump, view.talon));
+                if view.game_over {
+                    lines.push(match view.durak {
+                        Some(durak) => {
+                            let args = [self.player_name(durak)];
+                            describe(self.lang, EventMessage::GameOver, &args)
+                        }
+                        None => describe(self.lang, EventMessage::GameDrawn, &[]),
+                    });
+                }
+                lines.push(String::new());
+                for &(hash, count) in &view.hands {
+                    let role = if Some(hash) == view.attack_player {
+                        " attacking"
+                    } else if Some(hash) == view.target_player {
+                        " defending"
+                    } else {
+                        ""
+                    };
+                    let me = if Some(hash) == self.me { " (you)" } else { "" };
+                    lines.push(format!(
+                        "{}{}: {} cards{}",
+                        self.player_name(hash),
+                        me,
+                        count,
+                        role
+                    ));
+                }
+                lines.push(String::new());
+                for (i, (attack, defense)) in view.table_stacks.iter().enumerate() {
+                    lines.push(format!(
+                        "{} {} / {}",
+                        if i == self.selected_stack { ">" } else { " " },
+                        render_card(attack),
+                        defense.as_ref().map(render_card).unwrap_or_else(|| "--".into())
+                    ));
+                }
+            }
+        }
+        for (i, line) in lines.iter().take(height as usize - 6).enumerate() {
+            write!(screen, "{}{}", cursor::Goto(2, 3 + i as u16), truncate(line, split - 2))?;
+        }
+
+        write!(screen, "{}Chat", cursor::Goto(split + 2, 3))?;
+        let chat_height = height as usize - 8;
+        let skip = self.chat.len().saturating_sub(chat_height);
+        for (i, line) in self.chat.iter().skip(skip).enumerate() {
+            write!(
+                screen,
+                "{}{}",
+                cursor::Goto(split + 2, 4 + i as u16),
+                truncate(line, width - split - 2)
+            )?;
+        }
+
+        write!(screen, "{}", cursor::Goto(2, height - 3))?;
+        for (i, card) in self.hand().iter().enumerate() {
+            if i == self.selected_card {
+                write!(screen, "{}", style::Invert)?;
+            }
+            match card.suite {
+                Suite::Hearts | Suite::Diamonds => {
+                    write!(screen, "{}", color::Fg(color::Red))?
+                }
+                Suite::Clubs | Suite::Spades => {}
+            }
+            write!(screen, "{}{} ", render_card(card), style::Reset)?;
+        }
+
+        let help = match self.input {
+            Some((Input::Chat, ref text)) => format!("chat> {}", text),
+            Some((Input::NewTable, ref text)) => format!("table name> {}", text),
+            Some((Input::Password(_), ref text)) => format!("password> {}", "*".repeat(text.len())),
+            None if self.table.is_some() => {
+                "<-/-> card  up/down stack  a attack  d defend  t take  p pass  s start  y ready  \
+                 b bot  i info  m match  v stats  o top  c chat  l leave  q quit"
+                    .into()
+            }
+            None => self.status.clone(),
+        };
+        write!(screen, "{}{}", cursor::Goto(2, height - 1), truncate(&help, width - 2))?;
+        if self.input.is_none() && self.table.is_some() {
+            write!(screen, "{}{}", cursor::Goto(2, height), truncate(&self.status, width - 2))?;
+        }
+        screen.flush()?;
+        Ok(())
+    }
+}
+
+fn render_card(card: &Card) -> String {
+    match card.value {
+        CardValue::Number10 => format!("10{:?}", card.suite),
+        ref value => format!("{:?}{:?}", value, card.suite),
+    }
+}
+
+fn clamp(index: usize, len: usize) -> usize {
+    if len == 0 { 0 } else { index.min(len - 1) }
+}
+
+fn truncate(line: &str, width: u16) -> String {
+    line.chars().take(width as usize).collect()
+}
@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use rand::{thread_rng, Rng};
+use engine::*;
+use game::*;
+use ismcts::*;
+use network::*;
+use rules::*;
+
+// TODO import
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Computer player seated at a table like a client.
+pub trait Bot: Send {
+    /// Picks one of `actions`, the legal actions of `me` in `state`, or
+    /// `None` to wait. Bots may only look at their own hand and the public
+    /// part of the state.
+    fn choose(
+        &mut self,
+        me: &ClientHash,
+        state: &GameState,
+        players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Option<GameAction>;
+
+    /// Whether the bot is still thinking about a move and wants to be asked
+    /// again later.
+    fn pending(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum BotLevel {
+    Random,
+    #[default]
+    Heuristic,
+    Ismcts,
+    /// External engine configured under this name.
+    Engine(String),
+}
+
+/// Settings shared by all bots of a room or simulation.
+#[derive(Debug, Clone, Default)]
+pub struct BotConfig {
+    /// Time searching bots and engines should think about a move, one
+    /// second if unset.
+    pub budget: Option<Duration>,
+    /// Longest wait for the move of an engine, five seconds if unset.
+    pub engine_timeout: Option<Duration>,
+    /// Commands of the external engines by name, program first.
+    pub engines: HashMap<String, Vec<String>>,
+}
+
+const DEFAULT_BUDGET: Duration = Duration::from_secs(1);
+
+const DEFAULT_ENGINE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Plays any legal move.
+pub struct RandomBot;
+
+/// Defends with the lowest sufficient card, attacks with low cards and holds
+/// back trumps while the talon is not empty.
+pub struct HeuristicBot;
+
+impl BotLevel {
+    pub const ALL: &'static [BotLevel] =
+        &[BotLevel::Random, BotLevel::Heuristic, BotLevel::Ismcts];
+
+    pub fn name(&self) -> &str {
+        match *self {
+            BotLevel::Random => "random",
+            BotLevel::Heuristic => "heuristic",
+            BotLevel::Ismcts => "ismcts",
+            BotLevel::Engine(ref name) => name,
+        }
+    }
+
+    /// Creates a bot of this level. Searching bots play by `rules`, engines
+    /// are started from the commands in `config`.
+    pub fn create<T>(&self, rules: &T, config: &BotConfig) -> Result<Box<dyn Bot>>
+    where
+        T: GameRules + Clone + Send + 'static,
+    {
+        let budget = config.budget.unwrap_or(DEFAULT_BUDGET);
+        Ok(match *self {
+            BotLevel::Random => Box::new(RandomBot),
+            BotLevel::Heuristic => Box::new(HeuristicBot),
+            BotLevel::Ismcts => Box::new(IsmctsBot::new(rules.clone(), budget)),
+            BotLevel::Engine(ref name) => {
+                let command = match config.engines.get(name) {
+                    Some(command) => command,
+                    None => {
+                        return Err(durak_error!(
+                            UnknownBot,
+                            format!("Unknown bot level or engine {}.", name)
+                        ).arg(name.clone()))
+                    }
+                };
+                let timeout = config.engine_timeout.unwrap_or(DEFAULT_ENGINE_TIMEOUT);
+                Box::new(EngineBot::spawn(name.clone(), command, budget, timeout)?)
+            }
+        })
+    }
+}
+
+impl Bot for RandomBot {
+    fn choose(
+        &mut self,
+        _me: &ClientHash,
+        _state: &GameState,
+        _players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Option<GameAction> {
+        thread_rng().choose(actions).cloned()
+    }
+}
+
+impl Bot for HeuristicBot {
+    fn choose(
+        &mut self,
+        me: &ClientHash,
+        state: &GameState,
+        _players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Option<GameAction> {
+        let trump = state.trump.clone();
+        let cost = |card: &Card| card_cost(card, trump.as_ref());
+        if state.target_player == Some(*me) {
+            // Defend the first open stack as cheaply as possible.
+            let open = state.table_stacks.iter().position(|(_, b)| b.is_none());
+            let defense = actions
+                .iter()
+                .filter(|x| match **x {
+                    GameAction::PutCard(_, stack) => stack.is_some() && stack == open,
+                    _ => false,
+                })
+                .min_by_key(|x| match **x {
+                    GameAction::PutCard(ref card, _) => cost(card),
+                    _ => 0,
+                });
+            return match defense {
+                Some(action) => Some(action.clone()),
+                None => find(actions, |x| matches!(*x, GameAction::Take)),
+            };
+        }
+        let talon = !state.card_stack.is_empty();
+        let attack = actions
+            .iter()
+            .filter_map(|x| match *x {
+                GameAction::PutCard(ref card, None) => Some(card),
+                _ => None,
+            })
+            .filter(|card| {
+                // Only open with a trump or throw in valuable cards when the
+                // talon is gone.
+                state.table_stacks.is_empty() || !talon ||
+                    (Some(&card.suite) != trump.as_ref() && card.value < CardValue::Queen)
+            })
+            .min_by_key(|card| cost(card));
+        match attack {
+            Some(card) => Some(GameAction::PutCard(card.clone(), None)),
+            None => find(actions, |x| matches!(*x, GameAction::Pass)),
+        }
+    }
+}
+
+/// The player to act next when everybody waits for each other: the
+/// defender while a stack is open, otherwise the attackers who have not
+/// passed yet. Used to play games without humans.
+pub fn next_actor<T: GameRules>(
+    rules: &T,
+    state: &GameState,
+    players: &[ClientHash],
+) -> Option<(ClientHash, Vec<GameAction>)> {
+    if state.game_over {
+        return None;
+    }
+    let open = state.table_stacks.iter().any(|(_, b)| b.is_none());
+    let candidates = if open {
+        vec![state.target_player]
+    } else {
+        vec![state.attack_player, state.neighbor_player]
+    };
+    candidates
+        .into_iter()
+        .flatten()
+        .filter(|x| !state.passed.contains(x))
+        .map(|x| (x, rules.legal_actions(&x, state, players)))
+        .find(|(_, actions)| !actions.is_empty())
+}
+
+/// Orders cards by value with all trumps above the other suites.
+fn card_cost(card: &Card, trump: Option<&Suite>) -> usize {
+    let value = card.value.clone() as usize;
+    if Some(&card.suite) == trump { value + 9 } else { value }
+}
+
+fn find<F: Fn(&GameAction) -> bool>(actions: &[GameAction], f: F) -> Option<GameAction> {
+    actions.iter().find(|x| f(x)).cloned()
+}
+
+impl fmt::Display for BotLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for BotLevel {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<BotLevel> {
+        if let Some(level) = BotLevel::ALL.iter().find(|x| x.name() == s) {
+            return Ok(level.clone());
+        }
+        let valid = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+        if !s.is_empty() && s.chars().all(valid) {
+            Ok(BotLevel::Engine(s.into()))
+        } else {
+            Err(durak_error!(
+                UnknownBot,
+                format!("Unknown bot level {}.", s)
+            ).arg(s))
+        }
+    }
+}
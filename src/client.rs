@@ -0,0 +1,631 @@
+use std::collections::VecDeque;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use game::*;
+use i18n::*;
+use leaderboard::*;
+use network::*;
+use transport::*;
+
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Typed answer or notification received from the server.
+#[derive(Debug, Clone)]
+pub enum Event {
+    PlayerList(Vec<PlayerInfo>),
+    PlayerState(PlayerInfo),
+    TableList(Vec<TableInfo>),
+    Error(DurakError),
+    Chat(ClientHash, String),
+    GameState(GameView),
+    TableSettings(TableSettingsInfo),
+    MatchSummary(MatchInfo),
+    PlayerStats(StatsInfo),
+    Leaderboard(Leaderboard),
+    /// Seconds left for the move of the listed players.
+    MoveTimer(u64, Vec<ClientHash>),
+    /// A player at the table changed the name.
+    Renamed(ClientHash, String),
+    /// Seated players ready for the next game.
+    Ready(Vec<ClientHash>),
+    /// Seconds until the game starts, `None` when the start was called off.
+    Countdown(Option<u64>),
+    /// The owner removed us from the table.
+    Kicked(TableHash),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PlayerInfo {
+    pub hash: ClientHash,
+    pub name: String,
+    pub lang: Language,
+    pub table: Option<TableHash>,
+    /// Table followed as spectator.
+    pub watching: Option<TableHash>,
+    /// Games the player left before they ended.
+    pub abandoned: usize,
+    /// Whether the player is logged in to an account.
+    pub registered: bool,
+    /// Rating of the account, `None` for guests.
+    pub rating: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct TableInfo {
+    pub hash: TableHash,
+    pub players: usize,
+    pub min_players: usize,
+    pub max_players: usize,
+    pub state: TableState,
+    /// `open`, `password` or `private`.
+    pub access: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TableSettingsInfo {
+    pub hash: TableHash,
+    pub name: String,
+    pub owner: Option<ClientHash>,
+    pub players: usize,
+    pub min_players: usize,
+    pub max_players: usize,
+    pub locked: bool,
+    pub spectating: bool,
+    pub password: bool,
+    pub invite: Option<String>,
+    /// Seconds per move by phase, phases without a limit are left out.
+    pub timers: Vec<(TurnPhase, u64)>,
+    pub abandon_policy: AbandonPolicy,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MatchInfo {
+    pub hash: TableHash,
+    pub games: Option<usize>,
+    pub score: Option<usize>,
+    pub played: usize,
+    /// Durak count of every player, the leaders first.
+    pub standings: Vec<(ClientHash, usize)>,
+    pub over: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct StatsInfo {
+    pub name: String,
+    pub rating: f64,
+    pub games: usize,
+    pub durak: usize,
+    /// Share of the games not lost as durak.
+    pub win_rate: f64,
+    pub average_place: f64,
+    /// Games left before they ended.
+    pub abandoned: usize,
+    pub records: Vec<RecordInfo>,
+}
+
+/// Games in one variant with a fixed number of players.
+#[derive(Debug, Clone, Default)]
+pub struct RecordInfo {
+    pub variant: String,
+    pub players: usize,
+    pub games: usize,
+    pub durak: usize,
+    pub win_rate: f64,
+    pub average_place: f64,
+}
+
+/// A game state as seen by one player.
+#[derive(Debug, Clone, Default)]
+pub struct GameView {
+    pub cards: Vec<Card>,
+    /// Bottom card of the talon, `None` once the talon is empty.
+    pub trump_card: Option<Card>,
+    pub trump: Option<Suite>,
+    pub table_stacks: Vec<(Card, Option<Card>)>,
+    pub target_player: Option<ClientHash>,
+    pub attack_player: Option<ClientHash>,
+    /// Number of cards left in the talon.
+    pub talon: usize,
+    /// Number of cards in every player's hand.
+    pub hands: Vec<(ClientHash, usize)>,
+    /// Players without cards left, in the order they finished.
+    pub finished: Vec<ClientHash>,
+    pub game_over: bool,
+    /// Loser of a finished game, `None` for a draw.
+    pub durak: Option<ClientHash>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ListKind {
+    Players,
+    Tables,
+}
+
+/// Connection to a durak server speaking the text protocol.
+pub struct DurakClient {
+    writer: BufWriter<Box<dyn Write + Send>>,
+    events: mpsc::Receiver<Event>,
+    pending: Arc<Mutex<VecDeque<ListKind>>>,
+}
+
+impl DurakClient {
+    pub fn connect<A: ToSocketAddrs>(address: A) -> Result<DurakClient> {
+        DurakClient::from_connection(TcpStream::connect(address)?)
+    }
+
+    pub fn connect_unix<P: AsRef<Path>>(path: P) -> Result<DurakClient> {
+        DurakClient::from_connection(UnixStream::connect(path)?)
+    }
+
+    pub fn from_connection<C: Connection>(connection: C) -> Result<DurakClient> {
+        let reader = connection.try_clone()?;
+        let (tx, rx) = mpsc::channel();
+        let pending = Arc::new(Mutex::new(VecDeque::new()));
+        let expected = pending.clone();
+        thread::spawn(move || {
+            let mut parser = Parser::new(expected);
+            for line in BufReader::new(reader).lines() {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(_) => break,
+                };
+                if let Some(event) = parser.push(&line) {
+                    if tx.send(event).is_err() {
+                        break;
+                    }
+                }
+            }
+        });
+        Ok(DurakClient {
+            writer: BufWriter::new(Box::new(connection)),
+            events: rx,
+            pending,
+        })
+    }
+
+    pub fn send_player(&mut self, command: PlayerCommand) -> Result<()> {
+        if let PlayerCommand::List = command {
+            self.expect(ListKind::Players);
+        }
+        self.write_line(&format!("player {}", command))
+    }
+
+    pub fn send_table(&mut self, command: TableCommand) -> Result<()> {
+        if let TableCommand::List = command {
+            self.expect(ListKind::Tables);
+        }
+        self.write_line(&format!("table {}", command))
+    }
+
+    pub fn send_game(&mut self, command: GameCommand) -> Result<()> {
+        self.write_line(&format!("game {}", command))
+    }
+
+    pub fn set_name<S: Into<String>>(&mut self, name: S) -> Result<()> {
+        self.send_player(PlayerCommand::Name(name.into()))
+    }
+
+    pub fn register<S: Into<String>>(&mut self, name: S, password: S) -> Result<()> {
+        self.send_player(PlayerCommand::Register(name.into(), password.into()))
+    }
+
+    pub fn login<S: Into<String>>(&mut self, name: S, password: S) -> Result<()> {
+        self.send_player(PlayerCommand::Login(name.into(), password.into()))
+    }
+
+    /// Asks for the statistics of a registered player, one's own without a
+    /// name.
+    pub fn stats(&mut self, name: Option<String>) -> Result<()> {
+        self.send_player(PlayerCommand::Stats(name))
+    }
+
+    /// Asks for the best players of a variant, of all variants without one.
+    pub fn leaderboard(&mut self, variant: Option<String>, period: Period) -> Result<()> {
+        match variant {
+            Some(variant) => self.write_line(&format!("leaderboard {} {}", variant, period)),
+            None => self.write_line(&format!("leaderboard {}", period)),
+        }
+    }
+
+    pub fn join(&mut self, table: TableHash) -> Result<()> {
+        self.send_table(TableCommand::Join(table, None))
+    }
+
+    /// Joins a table protected by a password or invite code.
+    pub fn join_with<S: Into<String>>(&mut self, table: TableHash, secret: S) -> Result<()> {
+        self.send_table(TableCommand::Join(table, Some(secret.into())))
+    }
+
+    pub fn watch(&mut self, table: TableHash) -> Result<()> {
+        self.send_table(TableCommand::Watch(table, None))
+    }
+
+    pub fn chat<S: Into<String>>(&mut self, message: S) -> Result<()> {
+        self.send_table(TableCommand::Chat(message.into()))
+    }
+
+    pub fn put(&mut self, card: Card, stack: Option<usize>) -> Result<()> {
+        self.send_game(GameCommand::Action(GameAction::PutCard(card, stack)))
+    }
+
+    pub fn take(&mut self) -> Result<()> {
+        self.send_game(GameCommand::Action(GameAction::Take))
+    }
+
+    pub fn pass(&mut self) -> Result<()> {
+        self.send_game(GameCommand::Action(GameAction::Pass))
+    }
+
+    /// Declares whether the next game may start.
+    pub fn ready(&mut self, ready: bool) -> Result<()> {
+        self.send_game(GameCommand::Ready(ready))
+    }
+
+    /// Stream of everything the server sends, in order of arrival.
+    pub fn events(&self) -> &mpsc::Receiver<Event> {
+        &self.events
+    }
+
+    pub fn next_event(&self, timeout: Duration) -> Option<Event> {
+        self.events.recv_timeout(timeout).ok()
+    }
+
+    fn expect(&self, kind: ListKind) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.push_back(kind);
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Turns the lines of the text protocol back into events. Multi-line answers
+/// end with an empty line, lists are told apart by the order in which they
+/// were requested.
+struct Parser {
+    pending: Arc<Mutex<VecDeque<ListKind>>>,
+    block: Vec<String>,
+}
+
+impl Parser {
+    fn new(pending: Arc<Mutex<VecDeque<ListKind>>>) -> Parser {
+        Parser {
+            pending,
+            block: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, line: &str) -> Option<Event> {
+        if self.block.is_empty() {
+            if let Some(error) = line.strip_prefix("ERROR ") {
+                return Some(parse_error(error));
+            }
+            if let Some(chat) = line.strip_prefix("chat ") {
+                return parse_chat(chat).ok();
+            }
+            if let Some(renamed) = line.strip_prefix("renamed ") {
+                return match parse_chat(renamed) {
+                    Ok(Event::Chat(player, name)) => Some(Event::Renamed(player, name)),
+                    _ => None,
+                };
+            }
+            if line == "ready" || line.starts_with("ready ") {
+                return parse_ready(&line[5..]).ok();
+            }
+            if let Some(timer) = line.strip_prefix("timer ") {
+                return parse_move_timer(timer).ok();
+            }
+            if let Some(countdown) = line.strip_prefix("countdown ") {
+                return Some(Event::Countdown(countdown.trim().parse().ok()));
+            }
+            if let Some(table) = line.strip_prefix("kicked ") {
+                return parse_hash(table).ok().map(Event::Kicked);
+            }
+        }
+        if !line.is_empty() {
+            self.block.push(line.into());
+            return None;
+        }
+        let block: Vec<String> = self.block.drain(..).collect();
+        let result = match block.first() {
+            Some(first) if first.starts_with("hash  ") => parse_player_state(&block),
+            Some(first) if first.starts_with("settings ") => parse_table_settings(&block),
+            Some(first) if first.starts_with("match ") => parse_match_summary(&block),
+            Some(first) if first.starts_with("stats ") => parse_player_stats(&block),
+            Some(first) if first.starts_with("leaderboard ") => parse_leaderboard(&block),
+            Some(first) if first.starts_with("cards ") || first.starts_with("trump ") => {
+                parse_game_view(&block)
+            }
+            _ => {
+                let kind = self.pending.lock().ok().and_then(|mut x| x.pop_front());
+                match kind {
+                    Some(ListKind::Players) => parse_player_list(&block),
+                    Some(ListKind::Tables) => parse_table_list(&block),
+                    None => Err(durak_error!(InvalidArgument, "Unexpected list received.")),
+                }
+            }
+        };
+        Some(result.unwrap_or_else(Event::Error))
+    }
+}
+
+fn parse_hash(s: &str) -> Result<u64> {
+    u64::from_str_radix(s.trim(), 16).map_err(|_| {
+        durak_error!(InvalidArgument, "Could not parse hash.")
+    })
+}
+
+fn parse_cards(s: &str) -> Result<Vec<Card>> {
+    s.split_whitespace().map(|x| x.parse()).collect()
+}
+
+fn parse_error(line: &str) -> Event {
+    let mut parts = line.splitn(2, ' ');
+    let code = parts.next().and_then(|x| x.parse().ok()).unwrap_or(
+        ErrorCode::InvalidArgument,
+    );
+    Event::Error(DurakError::new(code, parts.next().unwrap_or("")))
+}
+
+fn parse_chat(line: &str) -> Result<Event> {
+    let mut parts = line.splitn(2, ' ');
+    let sender = parse_hash(parts.next().unwrap_or(""))?;
+    Ok(Event::Chat(sender, parts.next().unwrap_or("").into()))
+}
+
+fn parse_ready(line: &str) -> Result<Event> {
+    let players = line.split_whitespace().map(parse_hash).collect::<Result<_>>()?;
+    Ok(Event::Ready(players))
+}
+
+fn parse_move_timer(line: &str) -> Result<Event> {
+    let mut parts = line.split_whitespace();
+    let seconds = parts.next().unwrap_or("").parse()?;
+    let players = parts.map(parse_hash).collect::<Result<_>>()?;
+    Ok(Event::MoveTimer(seconds, players))
+}
+
+fn parse_player_list(block: &[String]) -> Result<Event> {
+    let mut players = Vec::new();
+    for line in block {
+        let mut parts = line.split_whitespace();
+        players.push(PlayerInfo {
+            hash: parse_hash(parts.next().unwrap_or(""))?,
+            name: parts.next().unwrap_or("").into(),
+            rating: parts.next().map(|x| x.parse()).transpose()?,
+            ..PlayerInfo::default()
+        });
+    }
+    Ok(Event::PlayerList(players))
+}
+
+fn parse_player_state(block: &[String]) -> Result<Event> {
+    let mut player = PlayerInfo::default();
+    for line in block {
+        let mut parts = line.splitn(2, ' ');
+        let value = parts.next().and(parts.next()).unwrap_or("").trim();
+        match line.split(' ').next() {
+            Some("hash") => player.hash = parse_hash(value)?,
+            Some("name") => player.name = value.into(),
+            Some("lang") => player.lang = value.parse()?,
+            Some("table") => player.table = Some(parse_hash(value)?),
+            Some("watch") => player.watching = Some(parse_hash(value)?),
+            Some("abandoned") => player.abandoned = value.parse()?,
+            Some("account") => player.registered = value == "yes",
+            Some("rating") => player.rating = Some(value.parse()?),
+            _ => {}
+        }
+    }
+    Ok(Event::PlayerState(player))
+}
+
+fn parse_table_settings(block: &[String]) -> Result<Event> {
+    let mut settings = TableSettingsInfo::default();
+    for line in block {
+        let mut parts = line.splitn(2, ' ');
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("").trim();
+        match key {
+            "settings" => settings.hash = parse_hash(value)?,
+            "name" => settings.name = value.into(),
+            "owner" => settings.owner = Some(parse_hash(value)?),
+            "players" => {
+                let numbers = value
+                    .split_whitespace()
+                    .map(|x| x.parse())
+                    .collect::<::std::result::Result<Vec<usize>, _>>()?;
+                if numbers.len() != 3 {
+                    return Err(durak_error!(InvalidArgument, "Could not parse table settings."));
+                }
+                settings.players = numbers[0];
+                settings.min_players = numbers[1];
+                settings.max_players = numbers[2];
+            }
+            "locked" => settings.locked = value == "yes",
+            "spectators" => settings.spectating = value == "on",
+            "password" => settings.password = value == "yes",
+            "invite" => settings.invite = Some(value.into()),
+            "abandon" => settings.abandon_policy = value.parse()?,
+            "timer" => {
+                let mut parts = value.split_whitespace();
+                let phase = parts.next().unwrap_or("").parse()?;
+                settings.timers.push((phase, parts.next().unwrap_or("").parse()?));
+            }
+            _ => {}
+        }
+    }
+    Ok(Event::TableSettings(settings))
+}
+
+fn parse_match_summary(block: &[String]) -> Result<Event> {
+    let mut summary = MatchInfo::default();
+    for line in block {
+        let mut parts = line.splitn(2, ' ');
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("").trim();
+        match key {
+            "match" => summary.hash = parse_hash(value)?,
+            "games" => summary.games = Some(value.parse()?),
+            "score" => summary.score = Some(value.parse()?),
+            "played" => summary.played = value.parse()?,
+            "player" => {
+                let mut parts = value.splitn(2, ' ');
+                let player = parse_hash(parts.next().unwrap_or(""))?;
+                summary.standings.push((player, parts.next().unwrap_or("").parse()?));
+            }
+            "over" => summary.over = value == "yes",
+            _ => {}
+        }
+    }
+    Ok(Event::MatchSummary(summary))
+}
+
+fn parse_player_stats(block: &[String]) -> Result<Event> {
+    let mut stats = StatsInfo::default();
+    for line in block {
+        let mut parts = line.splitn(2, ' ');
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("").trim();
+        match key {
+            "stats" => stats.name = value.into(),
+            "rating" => stats.rating = value.parse()?,
+            "games" => stats.games = value.parse()?,
+            "durak" => stats.durak = value.parse()?,
+            "winrate" => stats.win_rate = value.parse()?,
+            "place" => stats.average_place = value.parse()?,
+            "abandoned" => stats.abandoned = value.parse()?,
+            "record" => {
+                let parts: Vec<&str> = value.split_whitespace().collect();
+                if parts.len() != 6 {
+                    return Err(durak_error!(InvalidArgument, "Could not parse statistics."));
+                }
+                stats.records.push(RecordInfo {
+                    variant: parts[0].into(),
+                    players: parts[1].parse()?,
+                    games: parts[2].parse()?,
+                    durak: parts[3].parse()?,
+                    win_rate: parts[4].parse()?,
+                    average_place: parts[5].parse()?,
+                });
+            }
+            _ => {}
+        }
+    }
+    Ok(Event::PlayerStats(stats))
+}
+
+fn parse_leaderboard(block: &[String]) -> Result<Event> {
+    let mut leaderboard = Leaderboard {
+        variant: None,
+        period: Period::default(),
+        rating: Vec::new(),
+        wins: Vec::new(),
+    };
+    for line in block {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.as_slice() {
+            ["leaderboard", period] => leaderboard.period = period.parse()?,
+            ["leaderboard", variant, period] => {
+                leaderboard.variant = Some(variant.to_string());
+                leaderboard.period = period.parse()?;
+            }
+            ["rating", name, rating] => {
+                leaderboard.rating.push((name.to_string(), rating.parse()?))
+            }
+            ["won", name, wins] => leaderboard.wins.push((name.to_string(), wins.parse()?)),
+            _ => return Err(durak_error!(InvalidArgument, "Could not parse leaderboard.")),
+        }
+    }
+    Ok(Event::Leaderboard(leaderboard))
+}
+
+fn parse_table_list(block: &[String]) -> Result<Event> {
+    let mut tables = Vec::new();
+    for line in block {
+        let parts: Vec<&str> = line.splitn(6, ' ').collect();
+        // Names may contain spaces, the access follows as last word.
+        let (name, access) = match parts.get(5).and_then(|x| x.rsplit_once(' ')) {
+            Some(rest) => rest,
+            None => return Err(durak_error!(InvalidArgument, "Could not parse table list.")),
+        };
+        tables.push(TableInfo {
+            hash: parse_hash(parts[0])?,
+            players: parts[1].parse()?,
+            min_players: parts[2].parse()?,
+            max_players: parts[3].parse()?,
+            state: parts[4].parse()?,
+            access: access.into(),
+            name: name.into(),
+        });
+    }
+    Ok(Event::TableList(tables))
+}
+
+fn parse_game_view(block: &[String]) -> Result<Event> {
+    let mut view = GameView::default();
+    for line in block {
+        let mut parts = line.splitn(2, ' ');
+        let key = parts.next().unwrap_or("");
+        let value = parts.next().unwrap_or("").trim();
+        match key {
+            "cards" => view.cards = parse_cards(value)?,
+            "trump" => {
+                if let Some(suite) = value.strip_prefix('-') {
+                    view.trump = suite.parse().ok();
+                } else {
+                    let card: Card = value.parse()?;
+                    view.trump = Some(card.suite.clone());
+                    view.trump_card = Some(card);
+                }
+            }
+            "table" => {
+                for stack in value.split_whitespace() {
+                    let mut cards = stack.splitn(2, '/');
+                    let attack = cards.next().unwrap_or("").parse()?;
+                    let defense = match cards.next() {
+                        Some("--") | None => None,
+                        Some(card) => Some(card.parse()?),
+                    };
+                    view.table_stacks.push((attack, defense));
+                }
+            }
+            "target" => view.target_player = Some(parse_hash(value)?),
+            "attack" => view.attack_player = Some(parse_hash(value)?),
+            "talon" => view.talon = value.parse()?,
+            "hands" => {
+                for hand in value.split_whitespace() {
+                    let mut parts = hand.splitn(2, ':');
+                    let hash = parse_hash(parts.next().unwrap_or(""))?;
+                    view.hands.push((hash, parts.next().unwrap_or("").parse()?));
+                }
+            }
+            "out" => {
+                for hash in value.split_whitespace() {
+                    view.finished.push(parse_hash(hash)?);
+                }
+            }
+            "durak" => {
+                view.game_over = true;
+                if value != "-" {
+                    view.durak = Some(parse_hash(value)?);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(Event::GameState(view))
+}
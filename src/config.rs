@@ -0,0 +1,623 @@
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use getopts::{Matches, Options};
+use toml;
+use account::*;
+use bot::*;
+use game::*;
+use leaderboard::*;
+use logging::Level;
+use network::*;
+use rules::*;
+use simulate::*;
+use stats::*;
+
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+pub const DEFAULT_ADDRESS: &str = "0.0.0.0:2342";
+
+/// Seconds a table may stay without human players.
+pub const DEFAULT_EMPTY_TABLE_TIMEOUT: u64 = 300;
+
+/// Seconds a table may wait for its first game.
+pub const DEFAULT_IDLE_TABLE_TIMEOUT: u64 = 3600;
+
+/// Configuration keys of the move timers, `attack` is read from
+/// `[timers] attack` and `--attack-timer`.
+const MOVE_TIMER_KEYS: &[(TurnPhase, &str)] = &[
+    (TurnPhase::Attack, "attack"),
+    (TurnPhase::Defend, "defend"),
+    (TurnPhase::ThrowIn, "throw_in"),
+];
+
+/// Settings of the server binary, read from a TOML file and overridden by
+/// command line flags.
+///
+/// There is deliberately no snapshot path: tables and running games only
+/// live in memory and end with the server. What has to survive a restart,
+/// accounts, statistics and results, is written to its own file.
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub help: bool,
+    pub bind: Vec<String>,
+    pub unix: Option<PathBuf>,
+    pub unix_mode: Option<u32>,
+    pub cards_per_player: usize,
+    pub room: RoomConfig,
+    pub client_timeout: Option<Duration>,
+    pub log_level: Level,
+    pub log_file: Option<PathBuf>,
+    pub log_stderr: bool,
+    pub admin: Option<(String, String)>,
+    /// File of the registered accounts, accounts are lost on restart if
+    /// unset.
+    pub accounts: Option<PathBuf>,
+    /// Names only usable after logging in, next to the registered ones.
+    pub reserved_names: Vec<String>,
+    /// File of the player statistics and ratings, kept in memory if unset.
+    pub stats: Option<PathBuf>,
+    /// Log of every finished game the leaderboards are computed from, kept
+    /// in memory if unset.
+    pub results: Option<PathBuf>,
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config {
+            help: false,
+            bind: Vec::new(),
+            unix: None,
+            unix_mode: None,
+            cards_per_player: 5,
+            room: RoomConfig {
+                empty_table_timeout: Some(Duration::from_secs(DEFAULT_EMPTY_TABLE_TIMEOUT)),
+                idle_table_timeout: Some(Duration::from_secs(DEFAULT_IDLE_TABLE_TIMEOUT)),
+                ..RoomConfig::default()
+            },
+            client_timeout: None,
+            log_level: Level::Info,
+            log_file: None,
+            log_stderr: true,
+            admin: None,
+            accounts: None,
+            reserved_names: Vec::new(),
+            stats: None,
+            results: None,
+        }
+    }
+}
+
+impl Config {
+    pub fn options() -> Options {
+        let mut opts = Options::new();
+        opts.optopt("c", "config", "read settings from a TOML file", "FILE");
+        opts.optmulti("b", "bind", "listen on a TCP address", "ADDRESS");
+        opts.optopt("u", "unix", "listen on a Unix domain socket", "PATH");
+        opts.optopt("", "unix-mode", "permissions of the socket file", "MODE");
+        opts.optopt("", "cards", "cards dealt to every player", "NUMBER");
+        opts.optopt("", "max-tables", "maximum number of tables", "NUMBER");
+        opts.optopt("", "max-players", "maximum number of players", "NUMBER");
+        opts.optopt("", "timeout", "disconnect idle clients", "SECONDS");
+        opts.optopt("", "empty-table-timeout", "delete tables left empty", "SECONDS");
+        opts.optopt("", "idle-table-timeout", "delete tables never started", "SECONDS");
+        opts.optopt("", "start-countdown", "delay of the start once all are ready", "SECONDS");
+        opts.optopt("", "attack-timer", "time to open a round", "SECONDS");
+        opts.optopt("", "defend-timer", "time to defend", "SECONDS");
+        opts.optopt("", "throw-in-timer", "time to throw in more cards", "SECONDS");
+        opts.optopt("", "max-timeouts", "timeouts in a row that abandon a seat", "COUNT");
+        opts.optopt("", "abandon", "seats left in a game go to a bot or lose", "bot|durak");
+        opts.optopt("", "bot-time", "time bots may think per move", "MILLISECONDS");
+        engine_options(&mut opts);
+        opts.optopt("", "accounts", "file of the registered accounts", "PATH");
+        opts.optmulti("", "reserve", "name nobody may use as guest", "NAME");
+        opts.optopt("", "stats", "file of the player statistics", "PATH");
+        opts.optopt("", "results", "log of all finished games", "PATH");
+        opts.optopt("l", "log-level", "error, warn, info, debug or trace", "LEVEL");
+        opts.optopt("", "log-file", "append log messages to a file", "PATH");
+        opts.optflag("q", "quiet", "do not log to stderr");
+        opts.optflag("h", "help", "print this help");
+        opts
+    }
+
+    pub fn usage(program: &str) -> String {
+        Config::options().usage(&format!("Usage: {} [options]", program))
+    }
+
+    /// Parses the command line, loading the configuration file first if one
+    /// is given.
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Config> {
+        let matches = Config::options().parse(args).map_err(|e| {
+            durak_error!(InvalidConfig, e.to_string())
+        })?;
+        let mut config = match matches.opt_str("config") {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        config.apply_matches(&matches)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn load<P: Into<PathBuf>>(path: P) -> Result<Config> {
+        let path = path.into();
+        let mut content = String::new();
+        File::open(&path)?.read_to_string(&mut content)?;
+        let mut config = Config::default();
+        config.apply_toml(&content).map_err(|e| {
+            durak_error!(InvalidConfig, format!("{}: {}", path.display(), e))
+        })?;
+        Ok(config)
+    }
+
+    /// Addresses to listen on, falling back to the default TCP address when
+    /// neither TCP nor Unix domain sockets are configured.
+    pub fn addresses(&self) -> Vec<String> {
+        if self.bind.is_empty() && self.unix.is_none() {
+            vec![DEFAULT_ADDRESS.into()]
+        } else {
+            self.bind.clone()
+        }
+    }
+
+    fn apply_toml(&mut self, content: &str) -> Result<()> {
+        let value: toml::Value = content.parse().map_err(
+            |e: toml::de::Error| durak_error!(InvalidConfig, e.to_string()),
+        )?;
+        if let Some(server) = value.get("server") {
+            if let Some(bind) = server.get("bind") {
+                self.bind = match *bind {
+                    toml::Value::String(ref address) => vec![address.clone()],
+                    toml::Value::Array(ref addresses) => {
+                        let mut bind = Vec::new();
+                        for address in addresses {
+                            bind.push(toml_string(address, "server.bind")?);
+                        }
+                        bind
+                    }
+                    _ => return Err(invalid("server.bind")),
+                }
+            }
+            if let Some(unix) = server.get("unix") {
+                self.unix = Some(toml_string(unix, "server.unix")?.into());
+            }
+            if let Some(mode) = server.get("unix_mode") {
+                self.unix_mode = Some(match *mode {
+                    toml::Value::String(ref mode) => parse_mode(mode)?,
+                    // A plain 660 would be decimal and 0o660 cannot be told
+                    // apart from 432, so the mode has to be a string.
+                    toml::Value::Integer(_) => {
+                        return Err(durak_error!(
+                            InvalidConfig,
+                            "server.unix_mode has to be an octal string like \"660\"."
+                        ))
+                    }
+                    _ => return Err(invalid("server.unix_mode")),
+                });
+            }
+            if let Some(max) = server.get("max_tables") {
+                self.room.max_tables = Some(toml_usize(max, "server.max_tables")?);
+            }
+            if let Some(max) = server.get("max_players") {
+                self.room.max_players = Some(toml_usize(max, "server.max_players")?);
+            }
+            if let Some(level) = server.get("log_level") {
+                self.log_level = toml_string(level, "server.log_level")?.parse()?;
+            }
+            if let Some(file) = server.get("log_file") {
+                self.log_file = Some(toml_string(file, "server.log_file")?.into());
+            }
+            if let Some(stderr) = server.get("log_stderr") {
+                self.log_stderr = stderr.as_bool().ok_or_else(|| invalid("server.log_stderr"))?;
+            }
+        }
+        if let Some(rules) = value.get("rules") {
+            if let Some(cards) = rules.get("cards_per_player") {
+                self.cards_per_player = toml_usize(cards, "rules.cards_per_player")?;
+            }
+        }
+        if let Some(timeouts) = value.get("timeouts") {
+            if let Some(client) = timeouts.get("client") {
+                self.client_timeout = Some(Duration::from_secs(
+                    toml_usize(client, "timeouts.client")? as u64,
+                ));
+            }
+            if let Some(empty) = timeouts.get("empty_table") {
+                self.room.empty_table_timeout = Some(Duration::from_secs(
+                    toml_usize(empty, "timeouts.empty_table")? as u64,
+                ));
+            }
+            if let Some(idle) = timeouts.get("idle_table") {
+                self.room.idle_table_timeout = Some(Duration::from_secs(
+                    toml_usize(idle, "timeouts.idle_table")? as u64,
+                ));
+            }
+            if let Some(countdown) = timeouts.get("start_countdown") {
+                self.room.start_countdown = Some(Duration::from_secs(
+                    toml_usize(countdown, "timeouts.start_countdown")? as u64,
+                ));
+            }
+        }
+        if let Some(timers) = value.get("timers") {
+            for &(phase, key) in MOVE_TIMER_KEYS {
+                if let Some(seconds) = timers.get(key) {
+                    let seconds = toml_usize(seconds, &format!("timers.{}", key))? as u64;
+                    self.room.move_timers.set(phase, Some(Duration::from_secs(seconds)));
+                }
+            }
+            if let Some(max) = timers.get("max_timeouts") {
+                self.room.max_timeouts = Some(toml_usize(max, "timers.max_timeouts")?);
+            }
+            if let Some(policy) = timers.get("abandon") {
+                let policy = toml_string(policy, "timers.abandon")?;
+                self.room.abandon_policy = policy.parse().map_err(|_| invalid("timers.abandon"))?;
+            }
+        }
+        if let Some(bots) = value.get("bots") {
+            if let Some(time) = bots.get("think_time") {
+                self.room.bots.budget = Some(Duration::from_millis(
+                    toml_usize(time, "bots.think_time")? as u64,
+                ));
+            }
+            if let Some(timeout) = bots.get("engine_timeout") {
+                self.room.bots.engine_timeout = Some(Duration::from_millis(
+                    toml_usize(timeout, "bots.engine_timeout")? as u64,
+                ));
+            }
+        }
+        if let Some(engines) = value.get("engines") {
+            let engines = engines.as_table().ok_or_else(|| invalid("engines"))?;
+            for (name, command) in engines {
+                let key = format!("engines.{}", name);
+                let command = parse_command(&toml_string(command, &key)?, &key)?;
+                self.room.bots.engines.insert(name.clone(), command);
+            }
+        }
+        if let Some(admin) = value.get("admin") {
+            let name = match admin.get("name") {
+                Some(name) => toml_string(name, "admin.name")?,
+                None => return Err(invalid("admin.name")),
+            };
+            let password = match admin.get("password") {
+                Some(password) => toml_string(password, "admin.password")?,
+                None => return Err(invalid("admin.password")),
+            };
+            self.admin = Some((name, password));
+        }
+        if let Some(accounts) = value.get("accounts") {
+            if let Some(file) = accounts.get("file") {
+                self.accounts = Some(toml_string(file, "accounts.file")?.into());
+            }
+            if let Some(reserved) = accounts.get("reserved") {
+                let reserved = reserved.as_array().ok_or_else(|| invalid("accounts.reserved"))?;
+                for name in reserved {
+                    self.reserved_names.push(toml_string(name, "accounts.reserved")?);
+                }
+            }
+            if let Some(stats) = accounts.get("stats") {
+                self.stats = Some(toml_string(stats, "accounts.stats")?.into());
+            }
+            if let Some(results) = accounts.get("results") {
+                self.results = Some(toml_string(results, "accounts.results")?.into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Opens the account file, reserves the configured names and lets the
+    /// admin log in.
+    pub fn open_accounts(&self) -> Result<Accounts> {
+        let mut accounts = match self.accounts {
+            Some(ref path) => Accounts::open(path.clone())?,
+            None => Accounts::new(),
+        };
+        for name in &self.reserved_names {
+            accounts.reserve(name.clone());
+        }
+        if let Some((ref name, ref password)) = self.admin {
+            accounts.set_admin(name.clone(), password.clone());
+        }
+        Ok(accounts)
+    }
+
+    pub fn open_stats(&self) -> Result<Stats> {
+        match self.stats {
+            Some(ref path) => Stats::open(path.clone()),
+            None => Ok(Stats::new()),
+        }
+    }
+
+    pub fn open_results(&self) -> Result<ResultLog> {
+        match self.results {
+            Some(ref path) => ResultLog::open(path.clone()),
+            None => Ok(ResultLog::new()),
+        }
+    }
+
+    fn apply_matches(&mut self, matches: &Matches) -> Result<()> {
+        self.help = matches.opt_present("help");
+        let bind = matches.opt_strs("bind");
+        if !bind.is_empty() {
+            self.bind = bind;
+        }
+        if let Some(unix) = matches.opt_str("unix") {
+            self.unix = Some(unix.into());
+        }
+        if let Some(mode) = matches.opt_str("unix-mode") {
+            self.unix_mode = Some(parse_mode(&mode)?);
+        }
+        if let Some(cards) = matches.opt_str("cards") {
+            self.cards_per_player = parse_usize(&cards, "cards")?;
+        }
+        if let Some(max) = matches.opt_str("max-tables") {
+            self.room.max_tables = Some(parse_usize(&max, "max-tables")?);
+        }
+        if let Some(max) = matches.opt_str("max-players") {
+            self.room.max_players = Some(parse_usize(&max, "max-players")?);
+        }
+        if let Some(timeout) = matches.opt_str("timeout") {
+            self.client_timeout = Some(Duration::from_secs(
+                parse_usize(&timeout, "timeout")? as u64,
+            ));
+        }
+        if let Some(timeout) = matches.opt_str("empty-table-timeout") {
+            self.room.empty_table_timeout = Some(Duration::from_secs(
+                parse_usize(&timeout, "empty-table-timeout")? as u64,
+            ));
+        }
+        if let Some(timeout) = matches.opt_str("idle-table-timeout") {
+            self.room.idle_table_timeout = Some(Duration::from_secs(
+                parse_usize(&timeout, "idle-table-timeout")? as u64,
+            ));
+        }
+        if let Some(countdown) = matches.opt_str("start-countdown") {
+            self.room.start_countdown = Some(Duration::from_secs(
+                parse_usize(&countdown, "start-countdown")? as u64,
+            ));
+        }
+        for &(phase, key) in MOVE_TIMER_KEYS {
+            let option = format!("{}-timer", key.replace('_', "-"));
+            if let Some(seconds) = matches.opt_str(&option) {
+                let seconds = parse_usize(&seconds, &option)? as u64;
+                self.room.move_timers.set(phase, Some(Duration::from_secs(seconds)));
+            }
+        }
+        if let Some(max) = matches.opt_str("max-timeouts") {
+            self.room.max_timeouts = Some(parse_usize(&max, "max-timeouts")?);
+        }
+        if let Some(policy) = matches.opt_str("abandon") {
+            self.room.abandon_policy = policy.parse().map_err(|_| invalid("abandon"))?;
+        }
+        if let Some(time) = matches.opt_str("bot-time") {
+            self.room.bots.budget = Some(Duration::from_millis(
+                parse_usize(&time, "bot-time")? as u64,
+            ));
+        }
+        apply_engine_matches(&mut self.room.bots, matches)?;
+        if let Some(accounts) = matches.opt_str("accounts") {
+            self.accounts = Some(accounts.into());
+        }
+        self.reserved_names.extend(matches.opt_strs("reserve"));
+        if let Some(stats) = matches.opt_str("stats") {
+            self.stats = Some(stats.into());
+        }
+        if let Some(results) = matches.opt_str("results") {
+            self.results = Some(results.into());
+        }
+        if let Some(level) = matches.opt_str("log-level") {
+            self.log_level = level.parse()?;
+        }
+        if let Some(file) = matches.opt_str("log-file") {
+            self.log_file = Some(file.into());
+        }
+        if matches.opt_present("quiet") {
+            self.log_stderr = false;
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<()> {
+        // Dealing to two players has to leave a card in the talon that
+        // decides the trump.
+        let max_cards = (Card::deck().len() - 1) / MIN_PLAYERS;
+        if self.cards_per_player == 0 || self.cards_per_player > max_cards {
+            return Err(durak_error!(
+                InvalidConfig,
+                format!("Cards per player have to be between 1 and {}.", max_cards)
+            ));
+        }
+        if self.client_timeout == Some(Duration::from_secs(0)) {
+            return Err(durak_error!(InvalidConfig, "Timeout has to be positive."));
+        }
+        if let Some(ref accounts) = self.accounts {
+            if let Some(parent) = accounts.parent() {
+                if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                    return Err(durak_error!(
+                        InvalidConfig,
+                        format!("Account directory {} does not exist.", parent.display())
+                    ));
+                }
+            }
+        }
+        if let Some(ref stats) = self.stats {
+            if let Some(parent) = stats.parent() {
+                if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                    return Err(durak_error!(
+                        InvalidConfig,
+                        format!("Statistics directory {} does not exist.", parent.display())
+                    ));
+                }
+            }
+        }
+        if let Some(ref results) = self.results {
+            if let Some(parent) = results.parent() {
+                if !parent.as_os_str().is_empty() && !parent.is_dir() {
+                    return Err(durak_error!(
+                        InvalidConfig,
+                        format!("Results directory {} does not exist.", parent.display())
+                    ));
+                }
+            }
+        }
+        if let Some((ref name, ref password)) = self.admin {
+            if name.trim().is_empty() || password.is_empty() {
+                return Err(durak_error!(
+                    InvalidConfig,
+                    "Admin name and password must not be empty."
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings of `durakserver simulate`.
+#[derive(Debug, Clone)]
+pub struct SimulateConfig {
+    pub help: bool,
+    pub simulation: Simulation,
+    pub log_level: Level,
+}
+
+impl SimulateConfig {
+    pub fn options() -> Options {
+        let mut opts = Options::new();
+        opts.optopt("g", "games", "number of games (default 1000)", "NUMBER");
+        opts.optopt("s", "seed", "seed of the first deal (default 1)", "NUMBER");
+        opts.optopt("", "cards", "cards dealt to every player", "NUMBER");
+        opts.optopt("", "bot-time", "time bots may think per move", "MILLISECONDS");
+        engine_options(&mut opts);
+        opts.optopt("l", "log-level", "error, warn, info, debug or trace", "LEVEL");
+        opts.optflag("h", "help", "print this help");
+        opts
+    }
+
+    pub fn usage(program: &str) -> String {
+        let levels: Vec<&str> = BotLevel::ALL.iter().map(|x| x.name()).collect();
+        SimulateConfig::options().usage(&format!(
+            "Usage: {} simulate [options] BOT BOT [BOT...]\n\nBots: {} or an --engine name",
+            program,
+            levels.join(", ")
+        ))
+    }
+
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<SimulateConfig> {
+        let matches = SimulateConfig::options().parse(args).map_err(|e| {
+            durak_error!(InvalidConfig, e.to_string())
+        })?;
+        let mut bots = Vec::new();
+        for name in &matches.free {
+            bots.push(name.parse()?);
+        }
+        let mut config = SimulateConfig {
+            help: matches.opt_present("help"),
+            simulation: Simulation::new(bots),
+            log_level: Level::Warn,
+        };
+        let simulation = &mut config.simulation;
+        if let Some(games) = matches.opt_str("games") {
+            simulation.games = parse_usize(&games, "games")?;
+        }
+        if let Some(seed) = matches.opt_str("seed") {
+            simulation.seed = parse_usize(&seed, "seed")?;
+        }
+        if let Some(cards) = matches.opt_str("cards") {
+            simulation.cards_per_player = parse_usize(&cards, "cards")?;
+        }
+        if let Some(time) = matches.opt_str("bot-time") {
+            simulation.bot_config.budget =
+                Some(Duration::from_millis(parse_usize(&time, "bot-time")? as u64));
+        }
+        apply_engine_matches(&mut simulation.bot_config, &matches)?;
+        if let Some(level) = matches.opt_str("log-level") {
+            config.log_level = level.parse()?;
+        }
+        if !config.help {
+            // The same limits as at the tables, a card is left for the trump.
+            let cards = config.simulation.cards_per_player;
+            let max_cards = (Card::deck().len() - 1) / MIN_PLAYERS;
+            if cards == 0 || cards > max_cards {
+                return Err(durak_error!(
+                    InvalidConfig,
+                    format!("Cards per player have to be between 1 and {}.", max_cards)
+                ));
+            }
+            let max = DefaultRules::with_cards_per_player(cards).max_players();
+            let bots = config.simulation.bots.len();
+            if bots < MIN_PLAYERS || bots > max {
+                return Err(durak_error!(
+                    InvalidConfig,
+                    format!(
+                        "Between {} and {} bots can play with {} cards each.",
+                        MIN_PLAYERS,
+                        max,
+                        cards
+                    )
+                ));
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn engine_options(opts: &mut Options) {
+    opts.optmulti("", "engine", "external engine usable as bot", "NAME=COMMAND");
+    opts.optopt("", "engine-timeout", "longest wait for an engine move", "MILLISECONDS");
+}
+
+fn apply_engine_matches(bots: &mut BotConfig, matches: &Matches) -> Result<()> {
+    for engine in matches.opt_strs("engine") {
+        let mut parts = engine.splitn(2, '=');
+        match (parts.next(), parts.next()) {
+            (Some(name), Some(command)) if !name.is_empty() => {
+                let command = parse_command(command, "engine")?;
+                bots.engines.insert(name.into(), command);
+            }
+            _ => return Err(invalid("engine")),
+        }
+    }
+    if let Some(timeout) = matches.opt_str("engine-timeout") {
+        bots.engine_timeout = Some(Duration::from_millis(
+            parse_usize(&timeout, "engine-timeout")? as u64,
+        ));
+    }
+    Ok(())
+}
+
+/// Splits an engine command line at whitespace, the program comes first.
+fn parse_command(command: &str, key: &str) -> Result<Vec<String>> {
+    let command: Vec<String> = command.split_whitespace().map(|x| x.into()).collect();
+    if command.is_empty() {
+        return Err(invalid(key));
+    }
+    Ok(command)
+}
+
+fn invalid(key: &str) -> DurakError {
+    durak_error!(InvalidConfig, format!("Invalid or missing value for {}.", key))
+}
+
+fn toml_string(value: &toml::Value, key: &str) -> Result<String> {
+    value.as_str().map(|x| x.to_string()).ok_or_else(
+        || invalid(key),
+    )
+}
+
+fn toml_usize(value: &toml::Value, key: &str) -> Result<usize> {
+    match value.as_integer() {
+        Some(x) if x >= 0 => Ok(x as usize),
+        _ => Err(invalid(key)),
+    }
+}
+
+fn parse_usize(value: &str, key: &str) -> Result<usize> {
+    value.parse().map_err(|_| invalid(key))
+}
+
+fn parse_mode(value: &str) -> Result<u32> {
+    u32::from_str_radix(value.trim_start_matches("0o"), 8).map_err(|_| invalid("unix mode"))
+}
@@ -0,0 +1,233 @@
+//! External engines are local executables playing through a line based
+//! protocol on their standard input and output, similar to UCI for chess.
+//!
+//! The server starts the engine and sends `durak 1`, the protocol version.
+//! Whenever the engine has to move it receives a position:
+//!
+//! ```text
+//! position 00000000000000A1
+//! players 00000000000000A1 00000000000000B2
+//! cards 02 A3 64
+//! trump K1
+//! table 72/Q2 91/--
+//! target 00000000000000A1
+//! attack 00000000000000B2
+//! talon 13
+//! hands 00000000000000A1:3 00000000000000B2:4
+//! discard 62 J4
+//! known 00000000000000B2 03 83
+//! passed 00000000000000B2
+//! move put A3 1
+//! move take
+//! go 1000
+//! ```
+//!
+//! The lines up to `hands` (and `out`) are the game state as clients see it,
+//! `known` lists cards a player took or drew face up, `move` lines list the
+//! legal actions in the syntax of the `game` command and `go` carries the
+//! time the engine should think in milliseconds. The engine answers every
+//! `go` with exactly one line naming one of the moves, e.g. `put A3 1`. A
+//! late, missing or illegal answer is replaced by the heuristic bot's move.
+//! `quit` asks the engine to exit.
+
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use bot::*;
+use game::*;
+use network::*;
+use transport::*;
+
+// TODO import
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+pub const PROTOCOL_VERSION: usize = 1;
+
+/// Bot backed by an external engine process.
+pub struct EngineBot {
+    name: String,
+    child: Child,
+    stdin: ChildStdin,
+    replies: mpsc::Receiver<String>,
+    /// Positions sent without an answer yet, answers to all but the last
+    /// one are outdated.
+    unanswered: usize,
+    budget: Duration,
+    timeout: Duration,
+    waiting: Option<Waiting>,
+}
+
+struct Waiting {
+    state: GameState,
+    deadline: Instant,
+}
+
+impl EngineBot {
+    /// Starts `command`, the program followed by its arguments.
+    pub fn spawn<S: Into<String>>(
+        name: S,
+        command: &[String],
+        budget: Duration,
+        timeout: Duration,
+    ) -> Result<EngineBot> {
+        let name = name.into();
+        let program = match command.first() {
+            Some(program) => program,
+            None => {
+                return Err(durak_error!(
+                    InvalidConfig,
+                    format!("Engine {} has no command.", name)
+                ))
+            }
+        };
+        let mut child = Command::new(program)
+            .args(&command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let (stdin, stdout) = match (child.stdin.take(), child.stdout.take()) {
+            (Some(stdin), Some(stdout)) => (stdin, stdout),
+            _ => return Err(durak_error!(IoError, "Could not connect to engine.")),
+        };
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => {
+                        if tx.send(line).is_err() {
+                            break;
+                        }
+                    }
+                    Err(_) => break,
+                }
+            }
+        });
+        log_info!(name = name; "Engine started.");
+        let mut engine = EngineBot {
+            name,
+            child,
+            stdin,
+            replies: rx,
+            unanswered: 0,
+            budget,
+            timeout,
+            waiting: None,
+        };
+        engine.write(&format!("durak {}\n", PROTOCOL_VERSION))?;
+        Ok(engine)
+    }
+
+    fn write(&mut self, text: &str) -> Result<()> {
+        self.stdin.write_all(text.as_bytes())?;
+        self.stdin.flush()?;
+        Ok(())
+    }
+
+    /// Sends the position to the engine and tells it to think.
+    fn go(
+        &mut self,
+        me: &ClientHash,
+        state: &GameState,
+        players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Result<()> {
+        let mut buffer = Vec::new();
+        writeln!(buffer, "position {:016X}", me)?;
+        writeln!(buffer, "players {}", hashes(players))?;
+        write_game_state(&mut buffer, *me, state)?;
+        writeln!(buffer, "discard {}", cards(state.discard.iter()))?;
+        let mut known: Vec<(&ClientHash, _)> = state.revealed.iter().collect();
+        known.sort_by_key(|x| *x.0);
+        for (player, revealed) in known {
+            if player != me && !revealed.is_empty() {
+                writeln!(buffer, "known {:016X} {}", player, cards(revealed.iter()))?;
+            }
+        }
+        writeln!(buffer, "passed {}", hashes(&state.passed))?;
+        for action in actions {
+            writeln!(buffer, "move {}", GameCommand::Action(action.clone()))?;
+        }
+        writeln!(buffer, "go {}", self.budget.as_millis())?;
+        self.write(&String::from_utf8_lossy(&buffer))?;
+        self.unanswered += 1;
+        Ok(())
+    }
+}
+
+impl Bot for EngineBot {
+    fn choose(
+        &mut self,
+        me: &ClientHash,
+        state: &GameState,
+        players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Option<GameAction> {
+        let fallback = |reason: &str| {
+            log_warn!(client = *me; "Engine move replaced: {}", reason);
+            HeuristicBot.choose(me, state, players, actions).or_else(|| actions.first().cloned())
+        };
+        if let Some(waiting) = self.waiting.take() {
+            if waiting.state == *state {
+                loop {
+                    match self.replies.try_recv() {
+                        Ok(line) => {
+                            self.unanswered = self.unanswered.saturating_sub(1);
+                            if self.unanswered > 0 {
+                                continue;
+                            }
+                            log_debug!(client = *me, name = self.name; "Engine answered {}.", line);
+                            return match GameCommand::parse(line) {
+                                Ok(GameCommand::Action(ref action)) if actions.contains(action) => {
+                                    Some(action.clone())
+                                }
+                                _ => fallback("illegal answer"),
+                            };
+                        }
+                        Err(mpsc::TryRecvError::Empty) if Instant::now() < waiting.deadline => {
+                            self.waiting = Some(waiting);
+                            return None;
+                        }
+                        Err(mpsc::TryRecvError::Empty) => return fallback("timeout"),
+                        Err(mpsc::TryRecvError::Disconnected) => return fallback("engine exited"),
+                    }
+                }
+            }
+        }
+        if let Err(e) = self.go(me, state, players, actions) {
+            return fallback(&e.to_string());
+        }
+        self.waiting = Some(Waiting {
+            state: state.clone(),
+            deadline: Instant::now() + self.timeout,
+        });
+        None
+    }
+
+    fn pending(&self) -> bool {
+        self.waiting.is_some()
+    }
+}
+
+impl Drop for EngineBot {
+    fn drop(&mut self) {
+        let _ = self.write("quit\n");
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+        log_info!(name = self.name; "Engine stopped.");
+    }
+}
+
+fn hashes(players: &[ClientHash]) -> String {
+    players.iter().map(|x| format!("{:016X}", x)).collect::<Vec<_>>().join(" ")
+}
+
+fn cards<'a, I: Iterator<Item = &'a Card>>(cards: I) -> String {
+    let mut cards: Vec<&Card> = cards.collect();
+    cards.sort();
+    cards.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(" ")
+}
@@ -2,17 +2,23 @@ use rand::random;
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
+use std::time::{Duration, Instant};
+use account::*;
+use bot::*;
+use i18n::*;
+use leaderboard::*;
 use network::*;
 use rules::*;
+use stats::*;
 
 macro_rules! direct_error {
-    ($t:ident, $x: expr) => (Some((AnswerTarget::Direct,
-        Answer::Error(DurakError::new(DurakErrorType::$t, $x)))));
+    ($c:ident, $x: expr) => (Some((AnswerTarget::Direct,
+        Answer::Error(DurakError::new(ErrorCode::$c, $x)))));
 }
 
 // TODO import
 macro_rules! durak_error {
-    ($t:ident, $x:expr) => (DurakError::new(DurakErrorType::$t, $x))
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
 }
 
 pub type TableHash = u64;
@@ -22,12 +28,111 @@ pub struct Player {
     pub name: String,
     pub cards: Vec<Card>,
     pub table: Option<TableHash>,
+    pub lang: Language,
+    pub bot: Option<BotLevel>,
+    /// Table followed as spectator, never set together with `table`.
+    pub watching: Option<TableHash>,
+    /// Games left before they ended, remembered by name.
+    pub abandoned: usize,
+    /// Whether the player logged in to the account of the name.
+    pub registered: bool,
+    /// Rating of the account, `None` for guests.
+    pub rating: Option<f64>,
+    /// Whether the player logged in as server admin.
+    pub admin: bool,
 }
 
 pub struct Room<T: GameRules + Clone + Send> {
     players: HashMap<ClientHash, Player>,
     tables: HashMap<TableHash, Table<T>>,
     rules: T,
+    config: RoomConfig,
+    bots: HashMap<ClientHash, Box<dyn Bot>>,
+    accounts: Accounts,
+    /// Failed logins plus registrations per client with the time of the
+    /// last one.
+    password_attempts: HashMap<ClientHash, (u32, Instant)>,
+    stats: Stats,
+    results: ResultLog,
+}
+
+/// Longest player name in characters.
+pub const MAX_NAME_LENGTH: usize = 24;
+
+/// Seats of a new table unless the deck is too small for that many.
+const DEFAULT_MAX_PLAYERS: usize = 6;
+
+const DEFAULT_START_COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// Players are reminded of their move once this much time is left.
+const TIMER_WARNING: Duration = Duration::from_secs(10);
+
+/// Moves in a row a player may let time out before losing the seat.
+const DEFAULT_MAX_TIMEOUTS: usize = 3;
+
+/// Upper bound of bot actions in a row, guards against bots that never
+/// finish a round.
+const MAX_BOT_ACTIONS: usize = 1000;
+
+/// Password attempts a client may make before it has to wait between them.
+/// Checking a password derives a key on the room thread, which would stall
+/// every table if a client could do so at will.
+const FREE_PASSWORD_ATTEMPTS: u32 = 3;
+
+/// Wait after the first attempt beyond the free ones, doubled with each
+/// further attempt up to `MAX_PASSWORD_DELAY`.
+const PASSWORD_DELAY: Duration = Duration::from_secs(1);
+
+const MAX_PASSWORD_DELAY: Duration = Duration::from_secs(64);
+
+#[derive(Debug, Clone, Default)]
+pub struct RoomConfig {
+    pub max_tables: Option<usize>,
+    pub max_players: Option<usize>,
+    pub bots: BotConfig,
+    /// Tables without human players are deleted after this period.
+    pub empty_table_timeout: Option<Duration>,
+    /// Tables where no game was started are deleted after this period.
+    pub idle_table_timeout: Option<Duration>,
+    /// Delay of the start once every player is ready, three seconds if
+    /// unset.
+    pub start_countdown: Option<Duration>,
+    /// Move timers of new tables.
+    pub move_timers: MoveTimers,
+    /// What new tables do with the seat of a player who left a game.
+    pub abandon_policy: AbandonPolicy,
+    /// Moves in a row a player may let time out before the seat counts as
+    /// abandoned, three if unset.
+    pub max_timeouts: Option<usize>,
+}
+
+/// What happens to the seat of a player who disconnects, leaves or
+/// keeps letting the move timer run out during a game.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AbandonPolicy {
+    /// A bot plays on in place of the player.
+    #[default]
+    Bot,
+    /// The game ends with the player as durak.
+    Durak,
+}
+
+/// Longest time a player may think about a move, by phase of the round.
+/// Unset timers let players take as long as they want.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MoveTimers {
+    pub attack: Option<Duration>,
+    pub defend: Option<Duration>,
+    pub throw_in: Option<Duration>,
+}
+
+/// What the game waits for: the opening card of a round, the defense of
+/// open stacks or further cards once everything is defended.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TurnPhase {
+    Attack,
+    Defend,
+    ThrowIn,
 }
 
 #[derive(Clone, Debug)]
@@ -37,11 +142,59 @@ pub struct Table<T: GameRules + Clone + Send> {
     pub trump: Option<Suite>,
     pub max_players: usize,
     pub min_players: usize,
+    /// Player who created the table.
+    pub owner: Option<ClientHash>,
+    /// Players following the table without a seat.
+    pub spectators: Vec<ClientHash>,
+    pub spectating: bool,
+    /// Locked tables accept no new players.
+    pub locked: bool,
+    pub password: Option<String>,
+    /// Code to join a private table with, `None` for listed tables.
+    pub invite: Option<String>,
+    pub created: Instant,
+    /// Since when only bots or nobody sit at the table.
+    pub empty_since: Option<Instant>,
+    /// Number of games started at the table.
+    pub games: usize,
+    /// Players who want the next game to start.
+    pub ready: HashSet<ClientHash>,
+    /// When the game starts because everybody is ready.
+    pub countdown: Option<Instant>,
+    /// Series of games with the durak counted, if the owner set one up.
+    pub match_mode: Option<Match>,
+    pub timers: MoveTimers,
+    pub abandon_policy: AbandonPolicy,
+    /// Moves in a row each player let time out.
+    timeouts: HashMap<ClientHash, usize>,
+    /// Players the owner kicked, they may not sit down again.
+    banned: HashSet<ClientHash>,
+    /// Finished games not counted in the statistics yet.
+    results: Vec<GameState>,
+    /// When the current move was announced, reset by every action.
+    turn_started: Option<Instant>,
+    /// Whether the players were reminded that their time runs out.
+    turn_warned: bool,
     game_state: Option<GameState>,
     rules: T,
 }
 
-#[derive(Debug, Clone, Default)]
+/// Series of games at a table. It ends after a number of games or once a
+/// player was durak often enough, the fewest durak games win.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Match {
+    pub games: Option<usize>,
+    pub score: Option<usize>,
+    pub played: usize,
+    /// How often everybody who played in the match was durak.
+    pub durak_counts: HashMap<ClientHash, usize>,
+    /// Durak of the last game, who attacks first in the next one.
+    pub last_durak: Option<ClientHash>,
+    /// Whether the end of the match was announced.
+    pub reported: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct GameState {
     pub player_cards: HashMap<ClientHash, HashSet<Card>>,
     pub table_stacks: Vec<(Card, Option<Card>)>,
@@ -50,6 +203,21 @@ pub struct GameState {
     pub target_player: Option<ClientHash>,
     pub attack_player: Option<ClientHash>,
     pub neighbor_player: Option<ClientHash>,
+    /// Attackers who passed in the current round.
+    pub passed: Vec<ClientHash>,
+    /// Cards of successfully defended rounds.
+    pub discard: Vec<Card>,
+    /// Players without cards left, in the order they finished.
+    pub finished: Vec<ClientHash>,
+    /// Cards everybody has seen going into a player's hand, by taking or
+    /// drawing the trump card, and that were not played since.
+    pub revealed: HashMap<ClientHash, HashSet<Card>>,
+    pub durak: Option<ClientHash>,
+    pub game_over: bool,
+    /// Cards of other hands left out of a view, by player.
+    pub hidden_hands: HashMap<ClientHash, usize>,
+    /// Cards of the talon above the trump card left out of a view.
+    pub hidden_talon: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -58,13 +226,13 @@ pub enum TableState {
     Game,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct Card {
     pub value: CardValue,
     pub suite: Suite,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash, PartialOrd)]
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum CardValue {
     Number6,
     Number7,
@@ -77,7 +245,7 @@ pub enum CardValue {
     Ace,
 }
 
-#[derive(Clone, PartialEq, Eq, Hash)]
+#[derive(Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub enum Suite {
     Hearts,
     Diamonds,
@@ -91,6 +259,13 @@ impl Player {
             name: String::new(),
             cards: Vec::new(),
             table: None,
+            lang: Language::English,
+            bot: None,
+            watching: None,
+            abandoned: 0,
+            registered: false,
+            rating: None,
+            admin: false,
         }
     }
 }
@@ -104,12 +279,52 @@ impl fmt::Display for TableState {
     }
 }
 
-impl<T: GameRules + Clone + Send> Room<T> {
+impl<T: GameRules + Clone + Send + 'static> Room<T> {
     pub fn new(rules: T) -> Room<T> {
         Room {
             players: HashMap::new(),
             tables: HashMap::new(),
-            rules: rules,
+            rules,
+            config: RoomConfig::default(),
+            bots: HashMap::new(),
+            accounts: Accounts::new(),
+            password_attempts: HashMap::new(),
+            stats: Stats::new(),
+            results: ResultLog::new(),
+        }
+    }
+
+    pub fn set_config(&mut self, config: RoomConfig) {
+        self.config = config;
+    }
+
+    pub fn set_accounts(&mut self, accounts: Accounts) {
+        self.accounts = accounts;
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.stats = stats;
+    }
+
+    pub fn set_results(&mut self, results: ResultLog) {
+        self.results = results;
+    }
+
+    pub fn is_admin(&self, client: &ClientHash) -> bool {
+        self.players.get(client).is_some_and(|x| x.admin)
+    }
+
+    pub fn language(&self, client: &ClientHash) -> Language {
+        self.players.get(client).map(|x| x.lang).unwrap_or_default()
+    }
+
+    /// Prepares an answer for a single recipient: errors are translated and
+    /// game states only show what `client` may see.
+    pub fn answer_for(&self, client: &ClientHash, answer: Answer<T>) -> Answer<T> {
+        match answer {
+            Answer::Error(e) => Answer::Error(e.localized(self.language(client))),
+            Answer::GameState(state) => Answer::GameState(Box::new(state.view(client))),
+            answer => answer,
         }
     }
 
@@ -120,8 +335,73 @@ impl<T: GameRules + Clone + Send> Room<T> {
     ) -> Option<(AnswerTarget, Answer<T>)> {
         match command {
             Command::Player(PlayerCommand::Name(name)) => {
-                self.players.entry(*client).or_insert(Player::new()).name = name;
-                None
+                if let Err(e) = self.check_name(client, &name) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                let logged_in = self
+                    .players
+                    .get(client)
+                    .is_some_and(|x| x.registered && x.name == name);
+                if !logged_in && self.accounts.is_reserved(&name) {
+                    return direct_error!(NameReserved, "Name reserved, please log in.");
+                }
+                if self.is_full(client) {
+                    return direct_error!(ServerFull, "Maximum number of players reached.");
+                }
+                log_info!(client = *client, name = name; "Player named.");
+                self.claim_name(client, name, logged_in)
+            }
+            Command::Player(PlayerCommand::Register(name, password)) => {
+                if let Err(e) = self.check_name(client, &name) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                if self.is_full(client) {
+                    return direct_error!(ServerFull, "Maximum number of players reached.");
+                }
+                if let Some(e) = self.throttle_password(client) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                // Every registration counts, or a client could derive keys
+                // for new names endlessly.
+                self.count_password_attempt(client);
+                if let Err(e) = self.accounts.register(&name, &password) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                log_info!(client = *client, name = name; "Account registered.");
+                self.claim_name(client, name, true)
+            }
+            Command::Player(PlayerCommand::Login(name, password)) => {
+                if let Err(e) = self.check_name(client, &name) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                if let Some(e) = self.throttle_password(client) {
+                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                }
+                if !self.accounts.verify(&name, &password) {
+                    log_info!(client = *client, name = name; "Login failed.");
+                    self.count_password_attempt(client);
+                    return direct_error!(WrongPassword, "Wrong name or password.");
+                }
+                self.password_attempts.remove(client);
+                if self.is_full(client) {
+                    return direct_error!(ServerFull, "Maximum number of players reached.");
+                }
+                log_info!(client = *client, name = name; "Player logged in.");
+                self.claim_name(client, name, true)
+            }
+            Command::Player(PlayerCommand::Lang(lang)) => {
+                match self.players.get_mut(client) {
+                    Some(player) => {
+                        player.lang = lang;
+                        None
+                    }
+                    None => {
+                        direct_error!(
+                            PlayerNotFound,
+                            "Player not found. Please call \"player name\"."
+                        )
+                    }
+                }
             }
             Command::Player(PlayerCommand::List) => {
                 Some((
@@ -129,18 +409,48 @@ impl<T: GameRules + Clone + Send> Room<T> {
                     Answer::PlayerList(self.players.clone()),
                 ))
             }
+            Command::Player(PlayerCommand::Stats(name)) => {
+                let name = match name {
+                    Some(name) => name,
+                    None => match self.players.get(client) {
+                        Some(player) => player.name.clone(),
+                        None => return direct_error!(PlayerNotFound, "Player not found."),
+                    },
+                };
+                match self.player_stats(&name) {
+                    Some(stats) => Some((AnswerTarget::Direct, Answer::PlayerStats(stats))),
+                    None => direct_error!(NoStats, "No statistics for this player."),
+                }
+            }
             Command::Player(PlayerCommand::State) => {
                 match self.players.get(client) {
                     Some(player) => Some((
                         AnswerTarget::Direct,
                         Answer::PlayerState(*client, player.clone()),
                     )),
-                    None => direct_error!(GameError, "Player not found."),
+                    None => direct_error!(PlayerNotFound, "Player not found."),
                 }
             }
             Command::Table(tablecommand) => self.handle_table_command(client, tablecommand),
-            Command::Game(gamecommand) => self.handle_game_command(client, gamecommand),
-            Command::Answer(answer) => Some((AnswerTarget::Direct, answer)),
+            Command::Game(gamecommand) => {
+                match self.handle_game_command(client, gamecommand) {
+                    Some((AnswerTarget::List(targets), Answer::GameState(state))) => {
+                        let table = self.players.get(client).and_then(|x| x.table);
+                        let state = match table.and_then(|x| self.run_bots(x)) {
+                            Some(state) => Box::new(state),
+                            None => state,
+                        };
+                        self.record_results();
+                        Some((AnswerTarget::List(targets), Answer::GameState(state)))
+                    }
+                    answer => answer,
+                }
+            }
+            Command::Leaderboard(variant, period) => {
+                let leaderboard = self.results.leaderboard(variant.as_deref(), period, unix_time());
+                Some((AnswerTarget::Direct, Answer::Leaderboard(leaderboard)))
+            }
+            Command::Answer(answer) => Some((AnswerTarget::Direct, *answer)),
             Command::Quit => unimplemented!(),
         }
     }
@@ -151,78 +461,398 @@ impl<T: GameRules + Clone + Send> Room<T> {
         command: TableCommand,
     ) -> Option<(AnswerTarget, Answer<T>)> {
         match command {
-            TableCommand::New(name) => {
-                self.tables.insert(
-                    random(),
-                    Table::new(name, self.rules.clone()),
-                );
-                None
+            TableCommand::New(name, options) => {
+                if let Some(max) = self.config.max_tables {
+                    if self.tables.len() >= max {
+                        return direct_error!(TooManyTables, "Maximum number of tables reached.");
+                    }
+                }
+                match self.players.get(client) {
+                    Some(player) if player.table.is_some() => {
+                        return direct_error!(AlreadyJoined, "Already joined a table.");
+                    }
+                    Some(_) => {}
+                    None => {
+                        return direct_error!(
+                            PlayerNotFound,
+                            "Player not found. Please call \"player name\"."
+                        )
+                    }
+                }
+                self.stop_watching(client);
+                let tablehash = random();
+                log_info!(client = *client, table = tablehash, name = name; "Table created.");
+                let mut table = Table::new(name, self.rules.clone());
+                table.owner = Some(*client);
+                table.timers = self.config.move_timers;
+                table.abandon_policy = self.config.abandon_policy;
+                table.password = options.password;
+                if options.private {
+                    table.invite = Some(invite_code());
+                }
+                if options.min_players.is_some() || options.max_players.is_some() {
+                    let min = options.min_players.unwrap_or(table.min_players);
+                    let max = options.max_players.unwrap_or_else(|| table.max_players.max(min));
+                    if let Err(e) = table.set_player_limits(min, max) {
+                        return Some((AnswerTarget::Direct, Answer::Error(e)));
+                    }
+                }
+                // The creator sits down right away.
+                table.players.push(*client);
+                if let Some(player) = self.players.get_mut(client) {
+                    player.table = Some(tablehash);
+                }
+                let answer = Answer::TableSettings(tablehash, Box::new(table.clone()));
+                self.tables.insert(tablehash, table);
+                Some((AnswerTarget::Direct, answer))
             }
-            TableCommand::List => Some((
-                AnswerTarget::Direct,
-                Answer::TableList(self.tables.clone()),
-            )),
-            TableCommand::Join(tablehash) => {
+            TableCommand::List => {
+                // Private tables are only listed for their owner and those
+                // already at them.
+                let tables = self
+                    .tables
+                    .iter()
+                    .filter(|&(_, table)| {
+                        table.invite.is_none() || table.owner == Some(*client) ||
+                            table.audience().contains(client)
+                    })
+                    .map(|(tablehash, table)| (*tablehash, table.clone()))
+                    .collect();
+                Some((AnswerTarget::Direct, Answer::TableList(tables)))
+            }
+            TableCommand::Join(tablehash, secret) => {
+                if self.tables.contains_key(&tablehash) {
+                    self.stop_watching(client);
+                }
                 match self.tables.get_mut(&tablehash) {
                     Some(table) => {
-                        if (table.game_state.is_none()) &&
-                            (table.players.len() < table.max_players)
-                        {
+                        if table.game_state.is_some() {
+                            direct_error!(GameRunning, "Game already started.")
+                        } else if table.players.len() >= table.max_players {
+                            direct_error!(TableFull, "Table is full.")
+                        } else if table.locked {
+                            direct_error!(TableLocked, "Table is locked.")
+                        } else if table.banned.contains(client) {
+                            direct_error!(Banned, "Kicked from this table.")
+                        } else if !table.admits(client, secret.as_ref()) {
+                            direct_error!(WrongPassword, "Wrong or missing password.")
+                        } else {
                             if let Some(player) = self.players.get_mut(client) {
                                 if player.table.is_none() {
+                                    log_info!(client = *client, table = tablehash; "Table joined.");
                                     player.table = Some(tablehash);
                                     table.players.push(*client);
+                                    if table.owner.is_none() {
+                                        table.owner = Some(*client);
+                                    }
                                     None
                                 } else {
-                                    direct_error!(GameError, "Already joined a table.")
+                                    direct_error!(AlreadyJoined, "Already joined a table.")
                                 }
                             } else {
                                 direct_error!(
-                                    GameError,
+                                    PlayerNotFound,
                                     "Player not found. Please call \"player name\"."
                                 )
                             }
-                        } else {
-                            direct_error!(GameError, "Unable to join table.")
                         }
                     }
-                    None => direct_error!(GameError, "Table not found."),
+                    None => direct_error!(TableNotFound, "Table not found."),
                 }
             }
             TableCommand::Leave => {
+                if self.stop_watching(client) {
+                    return None;
+                }
                 if let Some(player) = self.players.get_mut(client) {
                     if let Some(tablehash) = player.table {
-                        player.table = None;
                         if let Some(table) = self.tables.get_mut(&tablehash) {
+                            if table.game_state.is_some() {
+                                return self.abandon(client, tablehash);
+                            }
+                            player.table = None;
+                            log_info!(client = *client, table = tablehash; "Table left.");
                             table.players.retain(|&x| x != *client);
+                            self.pass_ownership(tablehash, client);
                             None
                         } else {
-                            direct_error!(GameError, "Table not found.")
+                            player.table = None;
+                            direct_error!(TableNotFound, "Table not found.")
                         }
                     } else {
-                        direct_error!(GameError, "No table joined.")
+                        direct_error!(NoTableJoined, "No table joined.")
                     }
                 } else {
-                    direct_error!(GameError, "Player not found. Please call \"player name\".")
+                    direct_error!(PlayerNotFound, "Player not found. Please call \"player name\".")
+                }
+            }
+            TableCommand::AddBot(level) => {
+                if let Some(max) = self.config.max_players {
+                    if self.players.len() >= max {
+                        return direct_error!(ServerFull, "Maximum number of players reached.");
+                    }
+                }
+                // Seats are the owner's to give, like the ones they kick.
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                match self.tables.get_mut(&tablehash) {
+                    Some(table) => {
+                        if table.game_state.is_some() {
+                            direct_error!(GameRunning, "Game already started.")
+                        } else if table.players.len() >= table.max_players {
+                            direct_error!(TableFull, "Table is full.")
+                        } else if table.locked {
+                            direct_error!(TableLocked, "Table is locked.")
+                        } else {
+                            let bot = match level.create(&self.rules, &self.config.bots) {
+                                Ok(bot) => bot,
+                                Err(e) => {
+                                    return Some((AnswerTarget::Direct, Answer::Error(e)));
+                                }
+                            };
+                            let bothash: ClientHash = random();
+                            let player = Player {
+                                name: format!("{}-bot-{:04X}", level, bothash as u16),
+                                table: Some(tablehash),
+                                bot: Some(level),
+                                ..Player::new()
+                            };
+                            log_info!(
+                                client = *client,
+                                table = tablehash,
+                                bot = bothash,
+                                name = player.name;
+                                "Bot added."
+                            );
+                            table.players.push(bothash);
+                            self.players.insert(bothash, player);
+                            self.bots.insert(bothash, bot);
+                            None
+                        }
+                    }
+                    None => direct_error!(TableNotFound, "Table not found."),
                 }
             }
+            TableCommand::Watch(tablehash, secret) => {
+                match self.players.get_mut(client) {
+                    Some(player) => {
+                        if player.table.is_some() || player.watching.is_some() {
+                            return direct_error!(AlreadyJoined, "Already joined a table.");
+                        }
+                        match self.tables.get_mut(&tablehash) {
+                            Some(table) => {
+                                if !table.spectating {
+                                    return direct_error!(
+                                        SpectatingDisabled,
+                                        "Spectators are not allowed."
+                                    );
+                                }
+                                if !table.admits(client, secret.as_ref()) {
+                                    return direct_error!(
+                                        WrongPassword,
+                                        "Wrong or missing password."
+                                    );
+                                }
+                                log_info!(client = *client, table = tablehash; "Table watched.");
+                                player.watching = Some(tablehash);
+                                table.spectators.push(*client);
+                                // Catch up with a running game right away.
+                                table.game_state.as_ref().map(|state| {
+                                    let state = Box::new(state.clone());
+                                    (AnswerTarget::Direct, Answer::GameState(state))
+                                })
+                            }
+                            None => direct_error!(TableNotFound, "Table not found."),
+                        }
+                    }
+                    None => direct_error!(PlayerNotFound, "Player not found."),
+                }
+            }
+            TableCommand::Settings(None) => {
+                match self.players.get(client).and_then(|x| x.table) {
+                    Some(tablehash) => {
+                        match self.tables.get(&tablehash) {
+                            Some(table) => Some((
+                                AnswerTarget::Direct,
+                                Answer::TableSettings(tablehash, Box::new(table.clone())),
+                            )),
+                            None => direct_error!(TableNotFound, "Table not found."),
+                        }
+                    }
+                    None => direct_error!(NoTableJoined, "No table joined."),
+                }
+            }
+            TableCommand::Settings(Some(setting)) => {
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                let described = match setting {
+                    TableSetting::Password(Some(_)) => "password ***".to_string(),
+                    ref setting => setting.to_string(),
+                };
+                log_info!(client = *client, table = tablehash; "Table setting {}.", described);
+                if let Some(table) = self.tables.get_mut(&tablehash) {
+                    match setting {
+                        TableSetting::MinPlayers(_) | TableSetting::MaxPlayers(_)
+                            if table.game_state.is_some() =>
+                        {
+                            return direct_error!(GameRunning, "Game already started.");
+                        }
+                        TableSetting::MinPlayers(min) => {
+                            let max = table.max_players.max(min);
+                            if let Err(e) = table.set_player_limits(min, max) {
+                                return Some((AnswerTarget::Direct, Answer::Error(e)));
+                            }
+                        }
+                        TableSetting::MaxPlayers(max) => {
+                            let min = table.min_players;
+                            if let Err(e) = table.set_player_limits(min, max) {
+                                return Some((AnswerTarget::Direct, Answer::Error(e)));
+                            }
+                        }
+                        TableSetting::Name(name) => table.name = name,
+                        TableSetting::Password(password) => table.password = password,
+                        TableSetting::Private(true) => {
+                            if table.invite.is_none() {
+                                table.invite = Some(invite_code());
+                            }
+                        }
+                        TableSetting::Private(false) => table.invite = None,
+                        TableSetting::Abandon(policy) => table.abandon_policy = policy,
+                        TableSetting::Timer(phase, seconds) => {
+                            table.timers.set(phase, seconds.map(Duration::from_secs));
+                        }
+                        TableSetting::Spectators(allowed) => {
+                            table.spectating = allowed;
+                            if !allowed {
+                                for spectator in table.spectators.drain(..) {
+                                    if let Some(player) = self.players.get_mut(&spectator) {
+                                        player.watching = None;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                None
+            }
+            TableCommand::Kick(target) => {
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                if target == *client {
+                    return direct_error!(InvalidArgument, "Cannot kick yourself.");
+                }
+                if let Some(table) = self.tables.get_mut(&tablehash) {
+                    if !table.players.contains(&target) {
+                        return direct_error!(PlayerNotFound, "Player not at this table.");
+                    }
+                    if table.game_state.is_some() {
+                        return direct_error!(GameRunning, "Game already started.");
+                    }
+                    table.players.retain(|&x| x != target);
+                    table.banned.insert(target);
+                }
+                log_info!(client = *client, table = tablehash, kicked = target; "Player kicked.");
+                self.unseat(&target);
+                Some((AnswerTarget::List(vec![target]), Answer::Kicked(tablehash)))
+            }
+            TableCommand::Lock(locked) => {
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                log_info!(client = *client, table = tablehash, locked = locked; "Table lock set.");
+                if let Some(table) = self.tables.get_mut(&tablehash) {
+                    table.locked = locked;
+                }
+                None
+            }
+            TableCommand::Close => {
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                log_info!(client = *client, table = tablehash; "Table closed.");
+                self.remove_table(tablehash, "Table closed by its owner.")
+            }
+            TableCommand::Match(None) => {
+                match self.players.get(client).and_then(|x| x.table.or(x.watching)) {
+                    Some(tablehash) => {
+                        match self.tables.get(&tablehash) {
+                            Some(table) => {
+                                match table.match_mode {
+                                    Some(ref series) => Some((
+                                        AnswerTarget::Direct,
+                                        Answer::MatchSummary(tablehash, series.clone()),
+                                    )),
+                                    None => direct_error!(NoMatch, "No match running."),
+                                }
+                            }
+                            None => direct_error!(TableNotFound, "Table not found."),
+                        }
+                    }
+                    None => direct_error!(NoTableJoined, "No table joined."),
+                }
+            }
+            TableCommand::Match(Some(setting)) => {
+                let tablehash = match self.owned_table(client) {
+                    Ok(tablehash) => tablehash,
+                    Err(e) => return Some((AnswerTarget::Direct, Answer::Error(e))),
+                };
+                let table = match self.tables.get_mut(&tablehash) {
+                    Some(table) => table,
+                    None => return direct_error!(TableNotFound, "Table not found."),
+                };
+                if table.game_state.is_some() {
+                    return direct_error!(GameRunning, "Game already started.");
+                }
+                let series = match setting {
+                    MatchSetting::Games(0) | MatchSetting::Score(0) => {
+                        return direct_error!(InvalidArgument, "A match needs at least one game.");
+                    }
+                    MatchSetting::Games(games) => Match::new(Some(games), None, &table.players),
+                    MatchSetting::Score(score) => Match::new(None, Some(score), &table.players),
+                    MatchSetting::Off => {
+                        match table.match_mode.take() {
+                            Some(mut series) => {
+                                series.reported = true;
+                                series
+                            }
+                            None => return direct_error!(NoMatch, "No match running."),
+                        }
+                    }
+                };
+                log_info!(client = *client, table = tablehash; "Match set to {}.", setting);
+                if setting != MatchSetting::Off {
+                    table.match_mode = Some(series.clone());
+                }
+                Some((
+                    AnswerTarget::List(table.audience()),
+                    Answer::MatchSummary(tablehash, series),
+                ))
+            }
             TableCommand::Chat(message) => {
                 match self.players.get(client) {
                     Some(player) => {
-                        match player.table {
+                        match player.table.or(player.watching) {
                             Some(tablehash) => {
                                 match self.tables.get(&tablehash) {
                                     Some(table) => Some((
-                                        AnswerTarget::List(table.players.clone()),
+                                        AnswerTarget::List(table.audience()),
                                         Answer::Chat(*client, message),
                                     )),
-                                    None => direct_error!(GameError, "Table not found."),
+                                    None => direct_error!(TableNotFound, "Table not found."),
                                 }
                             }
-                            None => direct_error!(GameError, "No table joined yet."),
+                            None => direct_error!(NoTableJoined, "No table joined yet."),
                         }
                     }
-                    None => direct_error!(GameError, "Player not found."),
+                    None => direct_error!(PlayerNotFound, "Player not found."),
                 }
             }
         }
@@ -241,50 +871,73 @@ impl<T: GameRules + Clone + Send> Room<T> {
                             Some(tablehash) => {
                                 match self.tables.get_mut(&tablehash) {
                                     Some(table) => {
-                                        if table.players.len() >= table.min_players {
-                                            match table.game_state {
-                                                None => {
-                                                    let mut state = GameState::new();
-                                                    match table.rules.apply(
-                                                        client,
-                                                        &mut state,
-                                                        &table.players,
-                                                        GameAction::DealCards,
-                                                    ) {
-                                                        Ok(new_state) => {
-                                                            table.game_state = Some(state.clone());
-                                                            Some((
-                                                                AnswerTarget::List(
-                                                                    table.players.clone(),
-                                                                ),
-                                                                Answer::GameState(new_state.clone()),
-                                                            ))
-                                                        }
-                                                        Err(e) => Some((
-                                                            AnswerTarget::Direct,
-                                                            Answer::Error(e),
-                                                        )),
-                                                    }
+                                        if table.owner != Some(*client) {
+                                            direct_error!(
+                                                NotTableOwner,
+                                                "Only the owner can start the game."
+                                            )
+                                        } else {
+                                            match table.start(client) {
+                                                Ok(new_state) => {
+                                                    log_info!(
+                                                        client = *client,
+                                                        table = tablehash;
+                                                        "Game started."
+                                                    );
+                                                    Some((
+                                                        AnswerTarget::List(table.audience()),
+                                                        Answer::GameState(Box::new(new_state)),
+                                                    ))
                                                 }
-                                                Some(_) => {
-                                                    direct_error!(
-                                                        GameError,
-                                                        "Game already started."
-                                                    )
+                                                Err(e) => {
+                                                    Some((AnswerTarget::Direct, Answer::Error(e)))
                                                 }
                                             }
-                                        } else {
-                                            direct_error!(GameError, "Not enough players.")
                                         }
                                     }
-                                    None => direct_error!(GameError, "Table not found."),
+                                    None => direct_error!(TableNotFound, "Table not found."),
                                 }
                             }
-                            None => direct_error!(GameError, "No table joined."),
+                            None => direct_error!(NoTableJoined, "No table joined."),
+                        }
+                    }
+                    None => direct_error!(PlayerNotFound, "Player not found."),
+                }
+            }
+            GameCommand::Ready(ready) => {
+                let tablehash = match self.players.get(client) {
+                    Some(player) => {
+                        match player.table {
+                            Some(tablehash) => tablehash,
+                            None => return direct_error!(NoTableJoined, "No table joined."),
+                        }
+                    }
+                    None => return direct_error!(PlayerNotFound, "Player not found."),
+                };
+                match self.tables.get_mut(&tablehash) {
+                    Some(table) => {
+                        if table.game_state.is_some() {
+                            return direct_error!(GameRunning, "Game already started.");
+                        }
+                        if ready {
+                            table.ready.insert(*client);
+                        } else {
+                            table.ready.remove(client);
                         }
+                        log_info!(
+                            client = *client,
+                            table = tablehash,
+                            ready = ready;
+                            "Readiness changed."
+                        );
                     }
-                    None => direct_error!(GameError, "Player not found."),
+                    None => return direct_error!(TableNotFound, "Table not found."),
                 }
+                let table = &self.tables[&tablehash];
+                Some((
+                    AnswerTarget::List(table.audience()),
+                    Answer::Ready(self.ready_players(table)),
+                ))
             }
             GameCommand::Action(action) => {
                 match self.players.get(client) {
@@ -294,62 +947,630 @@ impl<T: GameRules + Clone + Send> Room<T> {
                                 match self.tables.get_mut(&tablehash) {
                                     Some(table) => {
                                         match table.game_state {
-                                            Some(ref mut state) => {
-                                                match table.rules.apply(
-                                                    client,
-                                                    state,
-                                                    &table.players,
-                                                    action,
-                                                ) {
-                                                    Ok(new_state) => Some((
-                                                        AnswerTarget::List(
-                                                            table.players.clone(),
-                                                        ),
-                                                        Answer::GameState(new_state),
-                                                    )),
+                                            Some(_) => {
+                                                log_debug!(
+                                                    client = *client,
+                                                    table = tablehash;
+                                                    "Game action {:?}.",
+                                                    action
+                                                );
+                                                match table.apply(client, action) {
+                                                    Ok(new_state) => {
+                                                        table.timeouts.remove(client);
+                                                        Some((
+                                                            AnswerTarget::List(table.audience()),
+                                                            Answer::GameState(Box::new(new_state)),
+                                                        ))
+                                                    }
                                                     Err(e) => Some((
                                                         AnswerTarget::Direct,
                                                         Answer::Error(e),
                                                     )),
                                                 }
                                             }
-                                            None => direct_error!(GameError, "No game running."),
+                                            None => direct_error!(NoGameRunning, "No game running."),
                                         }
                                     }
-                                    None => direct_error!(GameError, "Table not found."),
+                                    None => direct_error!(TableNotFound, "Table not found."),
                                 }
                             }
-                            None => direct_error!(GameError, "No table joined."),
+                            None => direct_error!(NoTableJoined, "No table joined."),
                         }
                     }
-                    None => direct_error!(GameError, "Player not found."),
+                    None => direct_error!(PlayerNotFound, "Player not found."),
                 }
             }
             GameCommand::State => {
                 match self.players.get(client) {
                     Some(player) => {
-                        match player.table {
+                        // Spectators get the public view like everybody else.
+                        match player.table.or(player.watching) {
                             Some(tablehash) => {
                                 match self.tables.get_mut(&tablehash) {
                                     Some(table) => {
                                         match table.game_state {
                                             Some(ref state) => Some((
                                                 AnswerTarget::Direct,
-                                                Answer::GameState(state.clone()),
+                                                Answer::GameState(Box::new(state.clone())),
                                             )),
-                                            None => direct_error!(GameError, "No game running."),
+                                            None => direct_error!(NoGameRunning, "No game running."),
                                         }
                                     }
-                                    None => direct_error!(GameError, "Table not found."),
+                                    None => direct_error!(TableNotFound, "Table not found."),
                                 }
                             }
-                            None => direct_error!(GameError, "No table joined."),
+                            None => direct_error!(NoTableJoined, "No table joined."),
+                        }
+                    }
+                    None => direct_error!(PlayerNotFound, "Player not found."),
+                }
+            }
+        }
+    }
+}
+
+impl<T: GameRules + Clone + Send + 'static> Room<T> {
+    /// Lets bots that finished thinking act. Returns the game states to send
+    /// to the players of the affected tables.
+    pub fn tick(&mut self) -> Vec<(AnswerTarget, Answer<T>)> {
+        let bots = &self.bots;
+        let waiting: Vec<TableHash> = self
+            .tables
+            .iter()
+            .filter(|&(_, table)| {
+                table.players.iter().any(|x| bots.get(x).map(|x| x.pending()).unwrap_or(false))
+            })
+            .map(|(tablehash, _)| *tablehash)
+            .collect();
+        let mut answers = Vec::new();
+        for tablehash in waiting {
+            if let Some(state) = self.run_bots(tablehash) {
+                if let Some(table) = self.tables.get(&tablehash) {
+                    answers.push((
+                        AnswerTarget::List(table.audience()),
+                        Answer::GameState(Box::new(state)),
+                    ));
+                }
+            }
+        }
+        answers.extend(self.check_ready());
+        answers.extend(self.report_matches());
+        answers.extend(self.check_turns());
+        self.record_results();
+        answers.extend(self.expire_tables());
+        answers
+    }
+
+    /// Announces the time players have for their move and plays for those
+    /// who let it run out.
+    fn check_turns(&mut self) -> Vec<(AnswerTarget, Answer<T>)> {
+        let now = Instant::now();
+        let mut answers = Vec::new();
+        let mut expired = Vec::new();
+        for (tablehash, table) in &mut self.tables {
+            let (phase, waiting) = match table.turn() {
+                Some(turn) => turn,
+                None => continue,
+            };
+            // Bots answer right away or are limited by their own timeout.
+            let bots = &self.bots;
+            let waiting: Vec<ClientHash> =
+                waiting.into_iter().filter(|x| !bots.contains_key(x)).collect();
+            let limit = match table.timers.get(phase) {
+                Some(limit) if !waiting.is_empty() => limit,
+                _ => continue,
+            };
+            let started = match table.turn_started {
+                Some(started) => started,
+                None => {
+                    table.turn_started = Some(now);
+                    table.turn_warned = false;
+                    answers.push((
+                        AnswerTarget::List(table.audience()),
+                        Answer::MoveTimer(limit.as_secs(), waiting),
+                    ));
+                    continue;
+                }
+            };
+            let elapsed = now.duration_since(started);
+            if elapsed >= limit {
+                expired.push((*tablehash, phase, waiting));
+            } else if !table.turn_warned && limit > TIMER_WARNING &&
+                limit - elapsed <= TIMER_WARNING
+            {
+                table.turn_warned = true;
+                answers.push((
+                    AnswerTarget::List(table.audience()),
+                    Answer::MoveTimer((limit - elapsed).as_secs(), waiting),
+                ));
+            }
+        }
+        let max_timeouts = self.config.max_timeouts.unwrap_or(DEFAULT_MAX_TIMEOUTS);
+        let mut abandoned = Vec::new();
+        for (tablehash, phase, waiting) in expired {
+            let mut last = None;
+            if let Some(table) = self.tables.get_mut(&tablehash) {
+                for player in waiting {
+                    // Neighbours who may throw in hold nobody up.
+                    if table.owes_move(&player, phase) {
+                        let timeouts = table.timeouts.entry(player).or_insert(0);
+                        *timeouts += 1;
+                        if *timeouts >= max_timeouts {
+                            abandoned.push((player, tablehash));
+                            continue;
+                        }
+                    }
+                    let action = match table.timeout_action(&player, phase) {
+                        Some(action) => action,
+                        None => continue,
+                    };
+                    log_info!(client = player, table = tablehash; "Move timed out, {:?}.", action);
+                    match table.apply(&player, action) {
+                        Ok(state) => last = Some(state),
+                        Err(e) => log_warn!(client = player; "Timeout action failed: {}", e),
+                    }
+                }
+                // Start over instead of retrying an action that failed.
+                table.turn_started = None;
+            }
+            if let Some(state) = self.run_bots(tablehash).or(last) {
+                answers.push((
+                    AnswerTarget::List(self.tables[&tablehash].audience()),
+                    Answer::GameState(Box::new(state)),
+                ));
+            }
+        }
+        for (player, tablehash) in abandoned {
+            let error = durak_error!(SeatAbandoned, "Seat lost after too many timeouts.");
+            answers.push((AnswerTarget::List(vec![player]), Answer::Error(error)));
+            answers.extend(self.abandon(&player, tablehash));
+        }
+        answers
+    }
+
+    /// Whether `client` would be one player too many.
+    fn is_full(&self, client: &ClientHash) -> bool {
+        self.config.max_players.is_some_and(|max| {
+            !self.players.contains_key(client) && self.players.len() >= max
+        })
+    }
+
+    /// Checks that `name` is valid and no other player uses it, ignoring
+    /// case.
+    fn check_name(&self, client: &ClientHash, name: &str) -> Result<()> {
+        validate_name(name)?;
+        let name = name.to_lowercase();
+        if self.players.iter().any(|(x, y)| x != client && y.name.to_lowercase() == name) {
+            return Err(durak_error!(NameTaken, "Name already in use."));
+        }
+        Ok(())
+    }
+
+    /// Gives `client` the name, creating the player if needed. The table
+    /// of a renamed player is told about the new name.
+    fn claim_name(
+        &mut self,
+        client: &ClientHash,
+        name: String,
+        registered: bool,
+    ) -> Option<(AnswerTarget, Answer<T>)> {
+        let player = self.players.entry(*client).or_default();
+        let renamed = !player.name.is_empty() && player.name != name;
+        player.name = name.clone();
+        // Guests start over with every connection, accounts keep the count.
+        if registered {
+            player.abandoned = self.stats.abandoned(&name);
+        }
+        player.registered = registered;
+        player.rating = if registered { Some(self.stats.rating(&name)) } else { None };
+        player.admin = registered && self.accounts.is_admin(&name);
+        let tablehash = player.table.or(player.watching)?;
+        let table = self.tables.get(&tablehash)?;
+        if renamed {
+            Some((AnswerTarget::List(table.audience()), Answer::Renamed(*client, name)))
+        } else {
+            None
+        }
+    }
+
+    /// Statistics of the registered player `name`, empty ones if the player
+    /// has not finished a game yet.
+    fn player_stats(&self, name: &str) -> Option<PlayerStats> {
+        match self.stats.get(name) {
+            Some(stats) => Some(stats.clone()),
+            None if self.accounts.contains(name) => Some(PlayerStats::new(name)),
+            None => None,
+        }
+    }
+
+    /// Logs the games finished since the last call, counts them in the
+    /// statistics and updates the ratings of the registered players.
+    fn record_results(&mut self) {
+        let mut results = Vec::new();
+        for table in self.tables.values_mut() {
+            let variant = table.rules.variant();
+            results.extend(table.results.drain(..).map(|x| (variant.clone(), x)));
+        }
+        if results.is_empty() {
+            return;
+        }
+        for (variant, state) in results {
+            let placings = state
+                .places()
+                .into_iter()
+                .filter_map(|(client, place)| {
+                    let player = self.players.get(&client)?;
+                    Some(Placing {
+                        name: player.name.clone(),
+                        place,
+                        durak: state.durak == Some(client),
+                        registered: player.registered,
+                    })
+                })
+                .collect();
+            let result = GameResult {
+                time: unix_time(),
+                variant,
+                placings,
+            };
+            if let Err(e) = self.stats.record(&result) {
+                log_error!("Unable to save statistics: {}", e);
+            }
+            if let Err(e) = self.results.append(result) {
+                log_error!("Unable to save game result: {}", e);
+            }
+        }
+        for player in self.players.values_mut().filter(|x| x.registered) {
+            player.rating = Some(self.stats.rating(&player.name));
+        }
+    }
+
+    /// Cleans up after a client whose connection was lost. A seat in a
+    /// running game counts as abandoned.
+    pub fn disconnect(&mut self, client: &ClientHash) -> Vec<(AnswerTarget, Answer<T>)> {
+        let mut answers = Vec::new();
+        let seated = self.players.get(client).is_some_and(|x| x.table.is_some());
+        if seated {
+            let leave = Command::Table(TableCommand::Leave);
+            if let Some((AnswerTarget::List(targets), answer)) =
+                self.handle_command(client, leave)
+            {
+                answers.push((AnswerTarget::List(targets), answer));
+            }
+        }
+        self.stop_watching(client);
+        // The name is needed to count a game the client forfeited.
+        self.record_results();
+        if self.players.remove(client).is_some() {
+            log_info!(client = *client; "Player removed.");
+        }
+        self.password_attempts.remove(client);
+        answers
+    }
+
+    /// Refuses a password attempt of `client` before its wait is over.
+    fn throttle_password(&self, client: &ClientHash) -> Option<DurakError> {
+        let &(attempts, last) = self.password_attempts.get(client)?;
+        if attempts < FREE_PASSWORD_ATTEMPTS {
+            return None;
+        }
+        let doublings = (attempts - FREE_PASSWORD_ATTEMPTS).min(16);
+        let delay = (PASSWORD_DELAY * 2u32.pow(doublings)).min(MAX_PASSWORD_DELAY);
+        let elapsed = last.elapsed();
+        if elapsed >= delay {
+            return None;
+        }
+        log_info!(client = *client; "Password attempt throttled.");
+        Some(durak_error!(
+            TooManyAttempts,
+            format!("Too many attempts, please wait {} seconds.", (delay - elapsed).as_secs() + 1)
+        ))
+    }
+
+    fn count_password_attempt(&mut self, client: &ClientHash) {
+        let now = Instant::now();
+        let entry = self.password_attempts.entry(*client).or_insert((0, now));
+        entry.0 += 1;
+        entry.1 = now;
+    }
+
+    /// Takes the seat in the running game at `tablehash` from `client`. The
+    /// policy of the table decides whether a bot plays on or the game ends
+    /// with `client` as durak. The abandonment is saved with the statistics
+    /// of registered players.
+    fn abandon(
+        &mut self,
+        client: &ClientHash,
+        tablehash: TableHash,
+    ) -> Option<(AnswerTarget, Answer<T>)> {
+        let name = match self.players.get_mut(client) {
+            Some(player) => {
+                player.table = None;
+                player.abandoned += 1;
+                if player.registered {
+                    if let Err(e) = self.stats.abandon(&player.name) {
+                        log_error!("Unable to save statistics: {}", e);
+                    }
+                }
+                player.name.clone()
+            }
+            None => return None,
+        };
+        let policy = self.tables.get(&tablehash)?.abandon_policy;
+        log_warn!(client = *client, table = tablehash, name = name; "Game abandoned.");
+        let bot = match policy {
+            AbandonPolicy::Bot => {
+                let level = BotLevel::default();
+                match level.create(&self.rules, &self.config.bots) {
+                    Ok(bot) => Some((level, bot)),
+                    Err(e) => {
+                        log_warn!(table = tablehash; "No bot to take the seat: {}", e);
+                        None
+                    }
+                }
+            }
+            AbandonPolicy::Durak => None,
+        };
+        let state = match bot {
+            Some((level, bot)) => {
+                let bothash: ClientHash = random();
+                let player = Player {
+                    name: format!("{}-bot-{:04X}", level, bothash as u16),
+                    table: Some(tablehash),
+                    bot: Some(level),
+                    ..Player::new()
+                };
+                log_info!(table = tablehash, bot = bothash, name = player.name; "Bot took over.");
+                self.players.insert(bothash, player);
+                self.bots.insert(bothash, bot);
+                let table = self.tables.get_mut(&tablehash)?;
+                table.replace_player(client, bothash);
+                let state = table.game_state.clone();
+                self.run_bots(tablehash).or(state)
+            }
+            None => {
+                let table = self.tables.get_mut(&tablehash)?;
+                let state = table.forfeit(client);
+                table.players.retain(|x| x != client);
+                state
+            }
+        };
+        self.pass_ownership(tablehash, client);
+        let table = self.tables.get(&tablehash)?;
+        state.map(|state| {
+            (AnswerTarget::List(table.audience()), Answer::GameState(Box::new(state)))
+        })
+    }
+
+    /// Gives the table to the longest seated human once `client`, its
+    /// owner, left.
+    fn pass_ownership(&mut self, tablehash: TableHash, client: &ClientHash) {
+        if let Some(table) = self.tables.get_mut(&tablehash) {
+            if table.owner == Some(*client) {
+                let bots = &self.bots;
+                table.owner = table.players.iter().cloned().find(|x| !bots.contains_key(x));
+                log_info!(table = tablehash, owner = table.owner; "Owner changed.");
+            }
+        }
+    }
+
+    /// Announces the result of matches that ended since the last call.
+    fn report_matches(&mut self) -> Vec<(AnswerTarget, Answer<T>)> {
+        let mut answers = Vec::new();
+        for (tablehash, table) in &mut self.tables {
+            let audience = table.audience();
+            if let Some(ref mut series) = table.match_mode {
+                if series.is_over() && !series.reported {
+                    log_info!(table = *tablehash, played = series.played; "Match finished.");
+                    series.reported = true;
+                    answers.push((
+                        AnswerTarget::List(audience),
+                        Answer::MatchSummary(*tablehash, series.clone()),
+                    ));
+                }
+            }
+        }
+        answers
+    }
+
+    /// Seated players ready for the next game, bots always are.
+    fn ready_players(&self, table: &Table<T>) -> Vec<ClientHash> {
+        table
+            .players
+            .iter()
+            .cloned()
+            .filter(|x| table.ready.contains(x) || self.bots.contains_key(x))
+            .collect()
+    }
+
+    /// Starts the countdown at tables where every seated player is ready,
+    /// cancels it when that changes and deals once it ran out.
+    fn check_ready(&mut self) -> Vec<(AnswerTarget, Answer<T>)> {
+        let now = Instant::now();
+        let countdown = self.config.start_countdown.unwrap_or(DEFAULT_START_COUNTDOWN);
+        let mut answers = Vec::new();
+        let mut due = Vec::new();
+        for (tablehash, table) in &mut self.tables {
+            let bots = &self.bots;
+            let players = &table.players;
+            table.ready.retain(|x| players.contains(x));
+            let all_ready = table.game_state.is_none() &&
+                table.players.len() >= table.min_players &&
+                !table.ready.is_empty() &&
+                table.players.iter().all(|x| table.ready.contains(x) || bots.contains_key(x));
+            match (table.countdown, all_ready) {
+                (None, true) => {
+                    log_info!(table = *tablehash; "Everybody ready.");
+                    table.countdown = Some(now + countdown);
+                    answers.push((
+                        AnswerTarget::List(table.audience()),
+                        Answer::Countdown(Some(countdown.as_secs())),
+                    ));
+                }
+                (Some(_), false) => {
+                    log_info!(table = *tablehash; "Countdown cancelled.");
+                    table.countdown = None;
+                    answers.push((AnswerTarget::List(table.audience()), Answer::Countdown(None)));
+                }
+                (Some(deadline), true) if now >= deadline => due.push(*tablehash),
+                _ => {}
+            }
+        }
+        for tablehash in due {
+            let started = match self.tables.get_mut(&tablehash) {
+                Some(table) => {
+                    let origin = table.players[0];
+                    table.start(&origin)
+                }
+                None => continue,
+            };
+            match started {
+                Ok(state) => {
+                    log_info!(table = tablehash; "Game started.");
+                    let state = self.run_bots(tablehash).unwrap_or(state);
+                    answers.push((
+                        AnswerTarget::List(self.tables[&tablehash].audience()),
+                        Answer::GameState(Box::new(state)),
+                    ));
+                }
+                Err(e) => log_warn!(table = tablehash; "Game not started: {}", e),
+            }
+        }
+        answers
+    }
+
+    /// Deletes tables without human players for longer than the configured
+    /// period and tables where no game was started in time.
+    fn expire_tables(&mut self) -> Vec<(AnswerTarget, Answer<T>)> {
+        let now = Instant::now();
+        let mut expired = Vec::new();
+        for (tablehash, table) in &mut self.tables {
+            let bots = &self.bots;
+            if table.players.iter().all(|x| bots.contains_key(x)) {
+                let since = *table.empty_since.get_or_insert(now);
+                if self.config.empty_table_timeout.is_some_and(|x| now - since >= x) {
+                    log_info!(table = *tablehash; "Empty table expired.");
+                    expired.push((*tablehash, "Table deleted because it was empty."));
+                    continue;
+                }
+            } else {
+                table.empty_since = None;
+            }
+            if table.games == 0 &&
+                self.config.idle_table_timeout.is_some_and(|x| now - table.created >= x)
+            {
+                log_info!(table = *tablehash; "Idle table expired.");
+                expired.push((*tablehash, "Table deleted because no game was started."));
+            }
+        }
+        expired
+            .into_iter()
+            .filter_map(|(tablehash, reason)| self.remove_table(tablehash, reason))
+            .collect()
+    }
+
+    /// Deletes a table and frees the seats at it. Returns the notification of
+    /// everybody who was at the table.
+    fn remove_table(
+        &mut self,
+        tablehash: TableHash,
+        reason: &str,
+    ) -> Option<(AnswerTarget, Answer<T>)> {
+        let table = self.tables.remove(&tablehash)?;
+        for player in &table.players {
+            self.unseat(player);
+        }
+        for spectator in &table.spectators {
+            if let Some(player) = self.players.get_mut(spectator) {
+                player.watching = None;
+            }
+        }
+        Some((
+            AnswerTarget::List(table.audience()),
+            Answer::Error(durak_error!(TableNotFound, reason)),
+        ))
+    }
+
+    /// Lets the bots at a table act until it is a human's turn again.
+    /// Returns the last state if any bot acted.
+    fn run_bots(&mut self, tablehash: TableHash) -> Option<GameState> {
+        let table = self.tables.get_mut(&tablehash)?;
+        let mut last = None;
+        for _ in 0..MAX_BOT_ACTIONS {
+            let state = match table.game_state {
+                Some(ref state) => state.clone(),
+                None => break,
+            };
+            let mut acted = false;
+            for player in table.players.clone() {
+                let bot = match self.bots.get_mut(&player) {
+                    Some(bot) => bot,
+                    None => continue,
+                };
+                let actions = table.rules.legal_actions(&player, &state, &table.players);
+                if actions.is_empty() {
+                    continue;
+                }
+                let view = state.view(&player);
+                if let Some(action) = bot.choose(&player, &view, &table.players, &actions) {
+                    log_debug!(client = player, table = tablehash; "Bot action {:?}.", action);
+                    match table.apply(&player, action) {
+                        Ok(new_state) => {
+                            last = Some(new_state);
+                            acted = true;
                         }
+                        Err(e) => log_warn!(client = player; "Illegal bot action: {}", e),
                     }
-                    None => direct_error!(GameError, "Player not found."),
+                    break;
                 }
             }
+            if !acted {
+                break;
+            }
         }
+        last
+    }
+
+    /// Table the client sits at if it owns it.
+    fn owned_table(&self, client: &ClientHash) -> Result<TableHash> {
+        let tablehash = match self.players.get(client) {
+            Some(player) => {
+                match player.table {
+                    Some(tablehash) => tablehash,
+                    None => return Err(durak_error!(NoTableJoined, "No table joined.")),
+                }
+            }
+            None => return Err(durak_error!(PlayerNotFound, "Player not found.")),
+        };
+        match self.tables.get(&tablehash) {
+            Some(table) if table.owner == Some(*client) => Ok(tablehash),
+            Some(_) => Err(durak_error!(NotTableOwner, "Not the owner of the table.")),
+            None => Err(durak_error!(TableNotFound, "Table not found.")),
+        }
+    }
+
+    /// Frees the seat of a player removed from its table. Bots have no
+    /// reason to stay and are removed entirely.
+    fn unseat(&mut self, client: &ClientHash) {
+        if self.bots.remove(client).is_some() {
+            self.players.remove(client);
+        } else if let Some(player) = self.players.get_mut(client) {
+            player.table = None;
+        }
+    }
+
+    /// Removes the client from the spectators of the table it watches.
+    /// Returns whether it was watching one.
+    fn stop_watching(&mut self, client: &ClientHash) -> bool {
+        let tablehash = match self.players.get_mut(client).and_then(|x| x.watching.take()) {
+            Some(tablehash) => tablehash,
+            None => return false,
+        };
+        if let Some(table) = self.tables.get_mut(&tablehash) {
+            log_info!(client = *client, table = tablehash; "Table no longer watched.");
+            table.spectators.retain(|x| x != client);
+        }
+        true
     }
 }
 
@@ -359,10 +1580,200 @@ impl<T: GameRules + Clone + Send> Table<T> {
             name: name.into(),
             players: Vec::new(),
             trump: None,
-            max_players: 6,
-            min_players: 2,
+            max_players: DEFAULT_MAX_PLAYERS.min(rules.max_players()),
+            min_players: MIN_PLAYERS,
+            owner: None,
+            spectators: Vec::new(),
+            spectating: true,
+            locked: false,
+            password: None,
+            invite: None,
+            created: Instant::now(),
+            empty_since: None,
+            games: 0,
+            ready: HashSet::new(),
+            countdown: None,
+            match_mode: None,
+            timers: MoveTimers::default(),
+            abandon_policy: AbandonPolicy::default(),
+            timeouts: HashMap::new(),
+            banned: HashSet::new(),
+            results: Vec::new(),
+            turn_started: None,
+            turn_warned: false,
             game_state: None,
-            rules: rules,
+            rules,
+        }
+    }
+
+    /// Applies `action` of `client` to the running game. A finished game is
+    /// removed so that the next one can be started.
+    pub fn apply(&mut self, client: &ClientHash, action: GameAction) -> Result<GameState> {
+        let state = match self.game_state {
+            Some(ref mut state) => self.rules.apply(client, state, &self.players, action)?,
+            None => return Err(durak_error!(NoGameRunning, "No game running.")),
+        };
+        self.turn_started = None;
+        if state.game_over {
+            log_info!(durak = state.durak; "Game finished.");
+            self.game_state = None;
+            if let Some(ref mut series) = self.match_mode {
+                series.record(&state, &self.players);
+            }
+            self.results.push(state.clone());
+        }
+        Ok(state)
+    }
+
+    /// Deals a new game. Readiness and a running countdown are reset.
+    pub fn start(&mut self, client: &ClientHash) -> Result<GameState> {
+        if self.game_state.is_some() {
+            return Err(durak_error!(GameRunning, "Game already started."));
+        }
+        if self.players.len() < self.min_players {
+            return Err(durak_error!(NotEnoughPlayers, "Not enough players."));
+        }
+        let mut order = self.players.clone();
+        if let Some(ref mut series) = self.match_mode {
+            if series.is_over() {
+                log_info!(played = series.played; "Rematch started.");
+                series.restart();
+            }
+            // The durak of the last game attacks first.
+            let durak = series.last_durak;
+            if let Some(first) = order.iter().position(|&x| Some(x) == durak) {
+                order.rotate_left(first);
+            }
+        }
+        let mut state = GameState::new();
+        let dealt = self.rules.apply(client, &mut state, &order, GameAction::DealCards)?;
+        self.game_state = Some(state);
+        self.turn_started = None;
+        self.games += 1;
+        self.ready.clear();
+        self.countdown = None;
+        Ok(dealt)
+    }
+
+    /// Changes how many players a game needs and allows. The deck has to be
+    /// enough for everybody and nobody seated loses the seat.
+    pub fn set_player_limits(&mut self, min: usize, max: usize) -> Result<()> {
+        let limit = self.rules.max_players();
+        if min < MIN_PLAYERS || min > max || max > limit || max < self.players.len() {
+            return Err(durak_error!(
+                InvalidPlayerLimits,
+                format!(
+                    "Between {} and {} players are possible, at least as many as seated.",
+                    MIN_PLAYERS,
+                    limit
+                )
+            ).arg(MIN_PLAYERS.to_string())
+                .arg(limit.to_string()));
+        }
+        self.min_players = min;
+        self.max_players = max;
+        Ok(())
+    }
+
+    /// Whether `secret` opens the table for `client`. Private tables want
+    /// the invite code, others their password if set. The owner always gets
+    /// in.
+    pub fn admits(&self, client: &ClientHash, secret: Option<&String>) -> bool {
+        if self.owner == Some(*client) {
+            return true;
+        }
+        match (&self.invite, &self.password) {
+            (Some(code), _) | (None, Some(code)) => secret == Some(code),
+            (None, None) => true,
+        }
+    }
+
+    /// Seated players followed by the spectators, the receivers of game
+    /// states and chat.
+    pub fn audience(&self) -> Vec<ClientHash> {
+        self.players.iter().chain(self.spectators.iter()).cloned().collect()
+    }
+
+    /// Seats `new` in place of `old`, in the running game as well.
+    fn replace_player(&mut self, old: &ClientHash, new: ClientHash) {
+        for player in &mut self.players {
+            if player == old {
+                *player = new;
+            }
+        }
+        if let Some(ref mut state) = self.game_state {
+            state.replace_player(old, new);
+        }
+        self.ready.remove(old);
+        self.timeouts.remove(old);
+    }
+
+    /// Ends the running game with `client` as durak.
+    fn forfeit(&mut self, client: &ClientHash) -> Option<GameState> {
+        let mut state = self.game_state.take()?;
+        state.game_over = true;
+        state.durak = Some(*client);
+        state.attack_player = None;
+        state.target_player = None;
+        state.neighbor_player = None;
+        log_info!(durak = state.durak; "Game forfeited.");
+        self.turn_started = None;
+        self.timeouts.clear();
+        if let Some(ref mut series) = self.match_mode {
+            series.record(&state, &self.players);
+        }
+        self.results.push(state.clone());
+        Some(state)
+    }
+
+    /// Phase of the running game and the players it waits for.
+    pub fn turn(&self) -> Option<(TurnPhase, Vec<ClientHash>)> {
+        let state = self.game_state.as_ref()?;
+        if state.table_stacks.iter().any(|(_, b)| b.is_none()) {
+            Some((TurnPhase::Defend, state.target_player.into_iter().collect()))
+        } else if state.table_stacks.is_empty() {
+            Some((TurnPhase::Attack, state.attack_player.into_iter().collect()))
+        } else {
+            let waiting = vec![state.attack_player, state.neighbor_player]
+                .into_iter()
+                .flatten()
+                .filter(|x| !state.passed.contains(x))
+                .collect();
+            Some((TurnPhase::ThrowIn, waiting))
+        }
+    }
+
+    /// Whether the game waits for `player` in `phase`: the defender has to
+    /// defend or take, the primary attacker to open or end the round.
+    fn owes_move(&self, player: &ClientHash, phase: TurnPhase) -> bool {
+        self.game_state.as_ref().is_some_and(|state| match phase {
+            TurnPhase::Defend => state.target_player == Some(*player),
+            TurnPhase::Attack | TurnPhase::ThrowIn => state.attack_player == Some(*player),
+        })
+    }
+
+    /// Move made for a player who let the timer of `phase` run out. The
+    /// defender takes and attackers pass, an opening attack cannot be
+    /// skipped and is made with the lowest card.
+    fn timeout_action(&self, player: &ClientHash, phase: TurnPhase) -> Option<GameAction> {
+        match phase {
+            TurnPhase::Defend => Some(GameAction::Take),
+            TurnPhase::ThrowIn => Some(GameAction::Pass),
+            TurnPhase::Attack => {
+                let state = self.game_state.as_ref()?;
+                self.rules.legal_actions(player, state, &self.players).into_iter().next()
+            }
+        }
+    }
+
+    /// How the table is joined: `open`, `password` or `private`.
+    pub fn get_access(&self) -> &'static str {
+        if self.invite.is_some() {
+            "private"
+        } else if self.password.is_some() {
+            "password"
+        } else {
+            "open"
         }
     }
 
@@ -374,6 +1785,76 @@ impl<T: GameRules + Clone + Send> Table<T> {
     }
 }
 
+impl Match {
+    pub fn new(games: Option<usize>, score: Option<usize>, players: &[ClientHash]) -> Match {
+        Match {
+            games,
+            score,
+            durak_counts: players.iter().map(|&x| (x, 0)).collect(),
+            ..Match::default()
+        }
+    }
+
+    /// Counts the result of a finished game played by `players`.
+    pub fn record(&mut self, state: &GameState, players: &[ClientHash]) {
+        self.played += 1;
+        for player in players {
+            self.durak_counts.entry(*player).or_insert(0);
+        }
+        if let Some(durak) = state.durak {
+            *self.durak_counts.entry(durak).or_insert(0) += 1;
+        }
+        self.last_durak = state.durak;
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.games.is_some_and(|games| self.played >= games) ||
+            self.score.is_some_and(|score| self.durak_counts.values().any(|&x| x >= score))
+    }
+
+    /// Plays the match again with the same limits. The last durak still
+    /// starts.
+    pub fn restart(&mut self) {
+        self.played = 0;
+        for count in self.durak_counts.values_mut() {
+            *count = 0;
+        }
+        self.reported = false;
+    }
+
+    /// Players ordered by how often they were durak, the leaders first.
+    pub fn standings(&self) -> Vec<(ClientHash, usize)> {
+        let mut standings: Vec<(ClientHash, usize)> =
+            self.durak_counts.iter().map(|(&x, &y)| (x, y)).collect();
+        standings.sort_by_key(|&(x, y)| (y, x));
+        standings
+    }
+}
+
+/// Checks the length and characters of a player name: letters and digits
+/// of any script as well as `.`, `-` and `_`.
+pub fn validate_name(name: &str) -> Result<()> {
+    let length = name.chars().count();
+    if length == 0 || length > MAX_NAME_LENGTH {
+        return Err(durak_error!(
+            InvalidName,
+            format!("Names have 1 to {} characters.", MAX_NAME_LENGTH)
+        ).arg(MAX_NAME_LENGTH.to_string()));
+    }
+    if !name.chars().all(|x| x.is_alphanumeric() || x == '.' || x == '-' || x == '_') {
+        return Err(durak_error!(
+            InvalidName,
+            "Names consist of letters, digits, dots, dashes and underscores."
+        ).arg(MAX_NAME_LENGTH.to_string()));
+    }
+    Ok(())
+}
+
+/// Random code shared to invite players to a private table.
+fn invite_code() -> String {
+    format!("{:08X}", random::<u32>())
+}
+
 impl GameState {
     pub fn new() -> GameState {
         GameState {
@@ -384,6 +1865,89 @@ impl GameState {
             target_player: None,
             attack_player: None,
             neighbor_player: None,
+            passed: Vec::new(),
+            discard: Vec::new(),
+            finished: Vec::new(),
+            revealed: HashMap::new(),
+            durak: None,
+            game_over: false,
+            hidden_hands: HashMap::new(),
+            hidden_talon: 0,
+        }
+    }
+
+    /// The state as `viewer` may see it: the own hand, the trump card and
+    /// everything public. Other hands and the talon are only counted, so
+    /// spectators see no cards in any hand.
+    pub fn view(&self, viewer: &ClientHash) -> GameState {
+        let mut view = self.clone();
+        for (player, cards) in &mut view.player_cards {
+            if player != viewer {
+                *view.hidden_hands.entry(*player).or_insert(0) += cards.len();
+                cards.clear();
+            }
+        }
+        if let Some(trump_card) = view.card_stack.pop() {
+            view.hidden_talon += view.card_stack.len();
+            view.card_stack = vec![trump_card];
+        }
+        view
+    }
+
+    /// Number of cards in the hand of `player`, also in a view.
+    pub fn hand_size(&self, player: &ClientHash) -> usize {
+        self.player_cards.get(player).map(|x| x.len()).unwrap_or(0) +
+            self.hidden_hands.get(player).cloned().unwrap_or(0)
+    }
+
+    /// Number of cards in the talon, also in a view.
+    pub fn talon_size(&self) -> usize {
+        self.card_stack.len() + self.hidden_talon
+    }
+
+    /// Finish position of every player, 1 for the first one out of cards.
+    /// Players still holding cards share the position after the finished
+    /// ones, the durak comes last.
+    pub fn places(&self) -> Vec<(ClientHash, usize)> {
+        let count = self.player_cards.len();
+        let mut places: Vec<(ClientHash, usize)> = self
+            .player_cards
+            .keys()
+            .map(|x| match self.finished.iter().position(|y| y == x) {
+                Some(i) => (*x, i + 1),
+                None if self.durak == Some(*x) => (*x, count),
+                None => (*x, self.finished.len() + 1),
+            })
+            .collect();
+        places.sort_by_key(|&(client, place)| (place, client));
+        places
+    }
+
+    /// Hands everything `old` has in the game over to `new`.
+    pub fn replace_player(&mut self, old: &ClientHash, new: ClientHash) {
+        if let Some(cards) = self.player_cards.remove(old) {
+            self.player_cards.insert(new, cards);
+        }
+        if let Some(cards) = self.revealed.remove(old) {
+            self.revealed.insert(new, cards);
+        }
+        if let Some(count) = self.hidden_hands.remove(old) {
+            self.hidden_hands.insert(new, count);
+        }
+        for player in self.passed.iter_mut().chain(self.finished.iter_mut()) {
+            if player == old {
+                *player = new;
+            }
+        }
+        for player in [
+            &mut self.target_player,
+            &mut self.attack_player,
+            &mut self.neighbor_player,
+            &mut self.durak,
+        ] {
+            if *player == Some(*old) {
+                *player = Some(new);
+            }
         }
     }
 }
@@ -405,9 +1969,31 @@ impl fmt::Display for GameState {
 impl Card {
     pub fn new(value: CardValue, suite: Suite) -> Card {
         Card {
-            value: value,
-            suite: suite,
+            value,
+            suite,
+        }
+    }
+
+    /// All 36 cards, ordered by suite and value.
+    pub fn deck() -> Vec<Card> {
+        let mut cards = Vec::new();
+        for suite in &[Suite::Hearts, Suite::Diamonds, Suite::Clubs, Suite::Spades] {
+            for value in &[
+                CardValue::Number6,
+                CardValue::Number7,
+                CardValue::Number8,
+                CardValue::Number9,
+                CardValue::Number10,
+                CardValue::Jack,
+                CardValue::Queen,
+                CardValue::King,
+                CardValue::Ace,
+            ]
+            {
+                cards.push(Card::new(value.clone(), suite.clone()));
+            }
         }
+        cards
     }
 
     pub fn better_as(&self, c: Card, trump: Suite) -> Option<bool> {
@@ -439,11 +2025,11 @@ impl fmt::Debug for Card {
 
 impl fmt::Display for Suite {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            &Suite::Diamonds => write!(f, "1"),
-            &Suite::Hearts => write!(f, "2"),
-            &Suite::Spades => write!(f, "3"),
-            &Suite::Clubs => write!(f, "4"),
+        match *self {
+            Suite::Diamonds => write!(f, "1"),
+            Suite::Hearts => write!(f, "2"),
+            Suite::Spades => write!(f, "3"),
+            Suite::Clubs => write!(f, "4"),
         }
     }
 }
@@ -495,21 +2081,114 @@ impl FromStr for Card {
             Some('Q') => CardValue::Queen,
             Some('K') => CardValue::King,
             Some('A') => CardValue::Ace,
-            Some(_) => return Err(durak_error!(ParserError, "Invalid card value specified.")),
-            None => return Err(durak_error!(ParserError, "No card value specified.")),
+            Some(_) => return Err(durak_error!(InvalidArgument, "Invalid card value specified.")),
+            None => return Err(durak_error!(MissingArgument, "No card value specified.")),
         };
         let suite = match chars.next() {
-            Some('1') => Suite::Diamonds,
-            Some('2') => Suite::Hearts,
-            Some('3') => Suite::Spades,
-            Some('4') => Suite::Clubs,
-            Some(_) => return Err(durak_error!(ParserError, "Invalid suite specified.")),
-            None => return Err(durak_error!(ParserError, "No suite specified.")),
+            Some(c) => c.to_string().parse()?,
+            None => return Err(durak_error!(MissingArgument, "No suite specified.")),
         };
 
         Ok(Card {
-            value: value,
-            suite: suite,
+            value,
+            suite,
         })
     }
 }
+
+impl FromStr for Suite {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<Suite> {
+        match s {
+            "1" => Ok(Suite::Diamonds),
+            "2" => Ok(Suite::Hearts),
+            "3" => Ok(Suite::Spades),
+            "4" => Ok(Suite::Clubs),
+            _ => Err(durak_error!(InvalidArgument, "Invalid suite specified.")),
+        }
+    }
+}
+
+impl MoveTimers {
+    pub fn get(&self, phase: TurnPhase) -> Option<Duration> {
+        match phase {
+            TurnPhase::Attack => self.attack,
+            TurnPhase::Defend => self.defend,
+            TurnPhase::ThrowIn => self.throw_in,
+        }
+    }
+
+    pub fn set(&mut self, phase: TurnPhase, timer: Option<Duration>) {
+        match phase {
+            TurnPhase::Attack => self.attack = timer,
+            TurnPhase::Defend => self.defend = timer,
+            TurnPhase::ThrowIn => self.throw_in = timer,
+        }
+    }
+}
+
+impl TurnPhase {
+    pub const ALL: &'static [TurnPhase] =
+        &[TurnPhase::Attack, TurnPhase::Defend, TurnPhase::ThrowIn];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            TurnPhase::Attack => "attack",
+            TurnPhase::Defend => "defend",
+            TurnPhase::ThrowIn => "throw-in",
+        }
+    }
+}
+
+impl fmt::Display for TurnPhase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for TurnPhase {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<TurnPhase> {
+        match TurnPhase::ALL.iter().find(|x| x.name() == s) {
+            Some(phase) => Ok(*phase),
+            None => Err(durak_error!(InvalidArgument, "Expected attack, defend or throw-in.")),
+        }
+    }
+}
+
+impl AbandonPolicy {
+    pub fn name(&self) -> &'static str {
+        match *self {
+            AbandonPolicy::Bot => "bot",
+            AbandonPolicy::Durak => "durak",
+        }
+    }
+}
+
+impl fmt::Display for AbandonPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for AbandonPolicy {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<AbandonPolicy> {
+        match s {
+            "bot" => Ok(AbandonPolicy::Bot),
+            "durak" => Ok(AbandonPolicy::Durak),
+            _ => Err(durak_error!(InvalidArgument, "Expected bot or durak.")),
+        }
+    }
+}
+
+impl FromStr for TableState {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<TableState> {
+        match s {
+            "Idle" => Ok(TableState::Idle),
+            "Game" => Ok(TableState::Game),
+            _ => Err(durak_error!(InvalidArgument, "Invalid table state.")),
+        }
+    }
+}
@@ -0,0 +1,278 @@
+use std::fmt;
+use std::str::FromStr;
+
+use network::*;
+
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+    Russian,
+}
+
+impl Language {
+    pub const ALL: &'static [Language] = &[Language::English, Language::German, Language::Russian];
+
+    pub fn code(&self) -> &'static str {
+        match *self {
+            Language::English => "en",
+            Language::German => "de",
+            Language::Russian => "ru",
+        }
+    }
+}
+
+/// Events clients describe to their players. The protocol only carries the
+/// arguments, the text is looked up with `describe`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventMessage {
+    /// Old and new name of a player.
+    Renamed,
+    /// Seconds until the game starts.
+    Countdown,
+    CountdownOff,
+    /// Players to move and the seconds they have left.
+    MoveTimer,
+    /// Name of the durak.
+    GameOver,
+    GameDrawn,
+    Kicked,
+}
+
+/// Looks up the message for `code` and fills in the `{0}`, `{1}`, ...
+/// placeholders from `args`.
+pub fn translate(language: Language, code: ErrorCode, args: &[String]) -> String {
+    let template = match language {
+        Language::English => english(code),
+        Language::German => german(code),
+        Language::Russian => russian(code),
+    };
+    fill(template, args)
+}
+
+/// Looks up the text for an event and fills in its arguments like
+/// `translate`.
+pub fn describe(language: Language, message: EventMessage, args: &[String]) -> String {
+    let template = match (language, message) {
+        (Language::English, EventMessage::Renamed) => "{0} is now called {1}.",
+        (Language::English, EventMessage::Countdown) => "Everybody ready, game starts in {0}s.",
+        (Language::English, EventMessage::CountdownOff) => "Start called off.",
+        (Language::English, EventMessage::MoveTimer) => "{0} to move, {1}s left.",
+        (Language::English, EventMessage::GameOver) => "Game over, {0} is the durak.",
+        (Language::English, EventMessage::GameDrawn) => "Game over, nobody lost.",
+        (Language::English, EventMessage::Kicked) => "The owner removed you from the table.",
+        (Language::German, EventMessage::Renamed) => "{0} heißt jetzt {1}.",
+        (Language::German, EventMessage::Countdown) => "Alle bereit, das Spiel beginnt in {0}s.",
+        (Language::German, EventMessage::CountdownOff) => "Start abgebrochen.",
+        (Language::German, EventMessage::MoveTimer) => "{0} am Zug, noch {1}s.",
+        (Language::German, EventMessage::GameOver) => "Spiel vorbei, {0} ist der Durak.",
+        (Language::German, EventMessage::GameDrawn) => "Spiel vorbei, niemand hat verloren.",
+        (Language::German, EventMessage::Kicked) => "Der Besitzer hat dich vom Tisch geworfen.",
+        (Language::Russian, EventMessage::Renamed) => "{0} теперь зовут {1}.",
+        (Language::Russian, EventMessage::Countdown) => "Все готовы, игра начнётся через {0} с.",
+        (Language::Russian, EventMessage::CountdownOff) => "Старт отменён.",
+        (Language::Russian, EventMessage::MoveTimer) => "Ходит {0}, осталось {1} с.",
+        (Language::Russian, EventMessage::GameOver) => "Игра окончена, {0} остался дураком.",
+        (Language::Russian, EventMessage::GameDrawn) => "Игра окончена, никто не проиграл.",
+        (Language::Russian, EventMessage::Kicked) => "Владелец выгнал вас из-за стола.",
+    };
+    fill(template, args)
+}
+
+fn fill(template: &str, args: &[String]) -> String {
+    args.iter().enumerate().fold(
+        template.to_string(),
+        |acc, (i, arg)| acc.replace(&format!("{{{}}}", i), arg),
+    )
+}
+
+fn english(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::IoError => "Input/output error.",
+        ErrorCode::ChannelSendError |
+        ErrorCode::ChannelRecvError => "Internal communication error.",
+        ErrorCode::UnknownCommand => "Unknown command {0}.",
+        ErrorCode::MissingArgument => "Missing argument.",
+        ErrorCode::InvalidArgument => "Invalid argument.",
+        ErrorCode::InvalidConfig => "Invalid configuration.",
+        ErrorCode::ServerFull => "Maximum number of players reached.",
+        ErrorCode::PlayerNotFound => "Player not found. Please call \"player name\".",
+        ErrorCode::TableNotFound => "Table not found.",
+        ErrorCode::TooManyTables => "Maximum number of tables reached.",
+        ErrorCode::TableFull => "Table is full.",
+        ErrorCode::AlreadyJoined => "Already joined a table.",
+        ErrorCode::NoTableJoined => "No table joined.",
+        ErrorCode::NotEnoughPlayers => "Not enough players.",
+        ErrorCode::GameRunning => "Game already started.",
+        ErrorCode::NoGameRunning => "No game running.",
+        ErrorCode::InvalidGameState => "Invalid game state.",
+        ErrorCode::NotYourTurn => "It is not your turn.",
+        ErrorCode::CardNotInHand => "Card not found.",
+        ErrorCode::CardTooLow => "Defending is only possible with better card.",
+        ErrorCode::CardNotMatching => "Defending is only possible with matching card.",
+        ErrorCode::CardValueNotOnTable => "Attacking is only possible with existing card values.",
+        ErrorCode::StackNotFound => "Stack not found.",
+        ErrorCode::StackAlreadyDefended => "Card already defended.",
+        ErrorCode::TooManyStacks => "No more stacks than cards allowed.",
+        ErrorCode::NothingToTake => "No undefended cards to take.",
+        ErrorCode::CannotPass => "Passing is not possible now.",
+        ErrorCode::NotTableOwner => "Only the owner of the table may do this.",
+        ErrorCode::SpectatingDisabled => "Spectators are not allowed at this table.",
+        ErrorCode::TableLocked => "Table is locked.",
+        ErrorCode::WrongPassword => "Wrong or missing password.",
+        ErrorCode::InvalidPlayerLimits => {
+            "Between {0} and {1} players are possible, at least as many as seated."
+        }
+        ErrorCode::NoMatch => "No match is being played at this table.",
+        ErrorCode::SeatAbandoned => "You lost your seat after letting too many moves time out.",
+        ErrorCode::NameReserved => "This name belongs to an account, please log in.",
+        ErrorCode::InvalidName => {
+            "Names are 1 to {0} letters, digits, dots, dashes or underscores."
+        }
+        ErrorCode::NameTaken => "Another player already uses this name.",
+        ErrorCode::NoStats => "No statistics recorded for this player.",
+        ErrorCode::NotAdmin => "Only the server admin may do this.",
+        ErrorCode::UnknownLanguage => "Unknown language {0}.",
+        ErrorCode::UnknownBot => "Unknown bot level or engine {0}.",
+        ErrorCode::InvalidSetting => "Invalid value for table setting {0}.",
+        ErrorCode::Banned => "You were kicked from this table.",
+        ErrorCode::TooManyAttempts => "Too many failed attempts, please wait.",
+        ErrorCode::Unimplemented => "Unimplemented feature.",
+    }
+}
+
+fn german(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::IoError => "Ein-/Ausgabefehler.",
+        ErrorCode::ChannelSendError |
+        ErrorCode::ChannelRecvError => "Interner Kommunikationsfehler.",
+        ErrorCode::UnknownCommand => "Unbekannter Befehl {0}.",
+        ErrorCode::MissingArgument => "Fehlendes Argument.",
+        ErrorCode::InvalidArgument => "Ungültiges Argument.",
+        ErrorCode::InvalidConfig => "Ungültige Konfiguration.",
+        ErrorCode::ServerFull => "Maximale Anzahl an Spielern erreicht.",
+        ErrorCode::PlayerNotFound => "Spieler nicht gefunden. Bitte zuerst \"player name\" aufrufen.",
+        ErrorCode::TableNotFound => "Tisch nicht gefunden.",
+        ErrorCode::TooManyTables => "Maximale Anzahl an Tischen erreicht.",
+        ErrorCode::TableFull => "Der Tisch ist voll.",
+        ErrorCode::AlreadyJoined => "Du sitzt bereits an einem Tisch.",
+        ErrorCode::NoTableJoined => "Du sitzt an keinem Tisch.",
+        ErrorCode::NotEnoughPlayers => "Nicht genug Spieler.",
+        ErrorCode::GameRunning => "Das Spiel läuft bereits.",
+        ErrorCode::NoGameRunning => "Es läuft kein Spiel.",
+        ErrorCode::InvalidGameState => "Ungültiger Spielzustand.",
+        ErrorCode::NotYourTurn => "Du bist nicht am Zug.",
+        ErrorCode::CardNotInHand => "Diese Karte hast du nicht auf der Hand.",
+        ErrorCode::CardTooLow => "Abwehren ist nur mit einer höheren Karte möglich.",
+        ErrorCode::CardNotMatching => "Abwehren ist nur mit gleicher Farbe oder Trumpf möglich.",
+        ErrorCode::CardValueNotOnTable => {
+            "Angreifen ist nur mit Werten möglich, die bereits auf dem Tisch liegen."
+        }
+        ErrorCode::StackNotFound => "Stapel nicht gefunden.",
+        ErrorCode::StackAlreadyDefended => "Die Karte wurde bereits abgewehrt.",
+        ErrorCode::TooManyStacks => "Nicht mehr Angriffe als Karten des Verteidigers erlaubt.",
+        ErrorCode::NothingToTake => "Es liegen keine unverteidigten Karten zum Aufnehmen.",
+        ErrorCode::CannotPass => "Passen ist gerade nicht möglich.",
+        ErrorCode::NotTableOwner => "Nur der Besitzer des Tisches darf das.",
+        ErrorCode::SpectatingDisabled => "An diesem Tisch sind keine Zuschauer erlaubt.",
+        ErrorCode::TableLocked => "Der Tisch ist gesperrt.",
+        ErrorCode::WrongPassword => "Falsches oder fehlendes Passwort.",
+        ErrorCode::InvalidPlayerLimits => {
+            "{0} bis {1} Spieler sind möglich, mindestens so viele wie sitzen."
+        }
+        ErrorCode::NoMatch => "An diesem Tisch läuft kein Match.",
+        ErrorCode::SeatAbandoned => "Du hast deinen Platz verloren, weil zu viele Züge abgelaufen sind.",
+        ErrorCode::NameReserved => "Dieser Name gehört zu einem Konto, bitte melde dich an.",
+        ErrorCode::InvalidName => {
+            "Namen bestehen aus 1 bis {0} Buchstaben, Ziffern, Punkten, Binde- oder Unterstrichen."
+        }
+        ErrorCode::NameTaken => "Ein anderer Spieler benutzt diesen Namen bereits.",
+        ErrorCode::NoStats => "Für diesen Spieler gibt es keine Statistik.",
+        ErrorCode::NotAdmin => "Nur der Administrator des Servers darf das.",
+        ErrorCode::UnknownLanguage => "Unbekannte Sprache {0}.",
+        ErrorCode::UnknownBot => "Unbekannte Bot-Stufe oder Engine {0}.",
+        ErrorCode::InvalidSetting => "Ungültiger Wert für die Tischeinstellung {0}.",
+        ErrorCode::Banned => "Du wurdest von diesem Tisch geworfen.",
+        ErrorCode::TooManyAttempts => "Zu viele Fehlversuche, bitte warte.",
+        ErrorCode::Unimplemented => "Nicht implementierte Funktion.",
+    }
+}
+
+fn russian(code: ErrorCode) -> &'static str {
+    match code {
+        ErrorCode::IoError => "Ошибка ввода-вывода.",
+        ErrorCode::ChannelSendError |
+        ErrorCode::ChannelRecvError => "Внутренняя ошибка связи.",
+        ErrorCode::UnknownCommand => "Неизвестная команда {0}.",
+        ErrorCode::MissingArgument => "Не хватает аргумента.",
+        ErrorCode::InvalidArgument => "Недопустимый аргумент.",
+        ErrorCode::InvalidConfig => "Недопустимая конфигурация.",
+        ErrorCode::ServerFull => "Достигнуто максимальное число игроков.",
+        ErrorCode::PlayerNotFound => "Игрок не найден. Сначала выполните \"player name\".",
+        ErrorCode::TableNotFound => "Стол не найден.",
+        ErrorCode::TooManyTables => "Достигнуто максимальное число столов.",
+        ErrorCode::TableFull => "Стол заполнен.",
+        ErrorCode::AlreadyJoined => "Вы уже сидите за столом.",
+        ErrorCode::NoTableJoined => "Вы не сидите за столом.",
+        ErrorCode::NotEnoughPlayers => "Недостаточно игроков.",
+        ErrorCode::GameRunning => "Игра уже началась.",
+        ErrorCode::NoGameRunning => "Игра не идёт.",
+        ErrorCode::InvalidGameState => "Недопустимое состояние игры.",
+        ErrorCode::NotYourTurn => "Сейчас не ваш ход.",
+        ErrorCode::CardNotInHand => "У вас нет этой карты.",
+        ErrorCode::CardTooLow => "Отбиться можно только старшей картой.",
+        ErrorCode::CardNotMatching => "Отбиться можно только картой той же масти или козырем.",
+        ErrorCode::CardValueNotOnTable => "Подкидывать можно только карты того же достоинства.",
+        ErrorCode::StackNotFound => "Стопка не найдена.",
+        ErrorCode::StackAlreadyDefended => "Карта уже побита.",
+        ErrorCode::TooManyStacks => "Нельзя подкинуть больше карт, чем есть у отбивающегося.",
+        ErrorCode::NothingToTake => "Нет неотбитых карт, чтобы взять.",
+        ErrorCode::CannotPass => "Сейчас нельзя сказать «бито».",
+        ErrorCode::NotTableOwner => "Это может сделать только владелец стола.",
+        ErrorCode::SpectatingDisabled => "За этим столом нельзя наблюдать.",
+        ErrorCode::TableLocked => "Стол закрыт для новых игроков.",
+        ErrorCode::WrongPassword => "Неверный или отсутствующий пароль.",
+        ErrorCode::InvalidPlayerLimits => {
+            "Возможно от {0} до {1} игроков, но не меньше, чем уже сидит."
+        }
+        ErrorCode::NoMatch => "За этим столом не идёт матч.",
+        ErrorCode::SeatAbandoned => "Вы потеряли место, пропустив слишком много ходов.",
+        ErrorCode::NameReserved => "Это имя принадлежит аккаунту, войдите в него.",
+        ErrorCode::InvalidName => {
+            "Имя должно содержать от 1 до {0} букв, цифр, точек, дефисов или подчёркиваний."
+        }
+        ErrorCode::NameTaken => "Это имя уже использует другой игрок.",
+        ErrorCode::NoStats => "Для этого игрока нет статистики.",
+        ErrorCode::NotAdmin => "Это может сделать только администратор сервера.",
+        ErrorCode::UnknownLanguage => "Неизвестный язык {0}.",
+        ErrorCode::UnknownBot => "Неизвестный уровень бота или движок {0}.",
+        ErrorCode::InvalidSetting => "Недопустимое значение настройки стола {0}.",
+        ErrorCode::Banned => "Вас выгнали из-за этого стола.",
+        ErrorCode::TooManyAttempts => "Слишком много неудачных попыток, подождите.",
+        ErrorCode::Unimplemented => "Функция не реализована.",
+    }
+}
+
+impl fmt::Display for Language {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+impl FromStr for Language {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<Language> {
+        match Language::ALL.iter().find(|x| x.code() == s) {
+            Some(language) => Ok(*language),
+            None => Err(durak_error!(
+                UnknownLanguage,
+                format!("Unknown language {}.", s)
+            ).arg(s)),
+        }
+    }
+}
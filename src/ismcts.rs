@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use rand::{thread_rng, Rng};
+use bot::*;
+use game::*;
+use network::*;
+use rules::*;
+
+/// Exploration constant of the UCB1 selection.
+const EXPLORATION: f64 = 0.7;
+
+/// Upper bound of actions in a single playout.
+const MAX_PLAYOUT: usize = 1000;
+
+/// Plays by information set Monte Carlo tree search. Hidden cards are
+/// sampled anew for every iteration, consistent with everything the bot has
+/// seen. The search runs on its own thread so that the room keeps serving
+/// commands; until it is done `choose` returns `None`.
+pub struct IsmctsBot<T: GameRules + Clone + Send + 'static> {
+    rules: T,
+    budget: Duration,
+    blocking: bool,
+    thinking: Option<Thinking>,
+}
+
+struct Thinking {
+    state: GameState,
+    result: mpsc::Receiver<Option<GameAction>>,
+}
+
+struct Node {
+    action: Option<GameAction>,
+    actor: ClientHash,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    visits: f64,
+    reward: f64,
+    available: f64,
+}
+
+impl<T: GameRules + Clone + Send + 'static> IsmctsBot<T> {
+    pub fn new(rules: T, budget: Duration) -> IsmctsBot<T> {
+        IsmctsBot {
+            rules,
+            budget,
+            blocking: false,
+            thinking: None,
+        }
+    }
+
+    /// Searches on the calling thread instead, for games without a room.
+    pub fn blocking(mut self) -> IsmctsBot<T> {
+        self.blocking = true;
+        self
+    }
+}
+
+impl<T: GameRules + Clone + Send + 'static> Bot for IsmctsBot<T> {
+    fn choose(
+        &mut self,
+        me: &ClientHash,
+        state: &GameState,
+        players: &[ClientHash],
+        actions: &[GameAction],
+    ) -> Option<GameAction> {
+        if let Some(thinking) = self.thinking.take() {
+            if thinking.state == *state {
+                match thinking.result.try_recv() {
+                    Ok(action) => return action,
+                    Err(mpsc::TryRecvError::Empty) => {
+                        self.thinking = Some(thinking);
+                        return None;
+                    }
+                    Err(mpsc::TryRecvError::Disconnected) => {}
+                }
+            }
+        }
+        if actions.len() == 1 {
+            return actions.first().cloned();
+        }
+        if self.blocking {
+            return search(&self.rules, me, state, players, actions, self.budget);
+        }
+        let (tx, rx) = mpsc::channel();
+        let (rules, me, budget) = (self.rules.clone(), *me, self.budget);
+        let (search_state, players, actions) = (state.clone(), players.to_vec(), actions.to_vec());
+        thread::spawn(move || {
+            let _ = tx.send(search(&rules, &me, &search_state, &players, &actions, budget));
+        });
+        self.thinking = Some(Thinking {
+            state: state.clone(),
+            result: rx,
+        });
+        None
+    }
+
+    fn pending(&self) -> bool {
+        self.thinking.is_some()
+    }
+}
+
+/// Picks the most visited of `actions` after searching for `budget`.
+pub fn search<T: GameRules>(
+    rules: &T,
+    me: &ClientHash,
+    state: &GameState,
+    players: &[ClientHash],
+    actions: &[GameAction],
+    budget: Duration,
+) -> Option<GameAction> {
+    let mut rng = thread_rng();
+    let mut playout_bot = HeuristicBot;
+    let deadline = Instant::now() + budget;
+    let mut nodes = vec![Node::new(None, *me, None)];
+    let mut iterations = 0;
+    while iterations == 0 || Instant::now() < deadline {
+        iterations += 1;
+        let mut sim = determinize(state, me);
+        let mut node = 0;
+
+        // Selection and expansion, the root always belongs to the bot.
+        let mut turn = Some((*me, actions.to_vec()));
+        while let Some((actor, legal)) = turn {
+            let known: Vec<usize> = nodes[node]
+                .children
+                .iter()
+                .cloned()
+                .filter(|&x| {
+                    nodes[x].actor == actor &&
+                        nodes[x].action.as_ref().map(|a| legal.contains(a)).unwrap_or(false)
+                })
+                .collect();
+            let untried: Vec<&GameAction> = legal
+                .iter()
+                .filter(|a| !known.iter().any(|&x| nodes[x].action.as_ref() == Some(a)))
+                .collect();
+            for &child in &known {
+                nodes[child].available += 1.0;
+            }
+            let next = if let Some(action) = rng.choose(&untried) {
+                let child = nodes.len();
+                nodes.push(Node::new(Some((*action).clone()), actor, Some(node)));
+                nodes[node].children.push(child);
+                child
+            } else {
+                match known.iter().cloned().max_by(
+                    |&a, &b| nodes[a].ucb().total_cmp(&nodes[b].ucb()),
+                ) {
+                    Some(child) => child,
+                    None => break,
+                }
+            };
+            let action = nodes[next].action.clone().unwrap();
+            if rules.apply(&actor, &mut sim, players, action).is_err() {
+                break;
+            }
+            node = next;
+            if nodes[node].visits == 0.0 {
+                break;
+            }
+            turn = next_actor(rules, &sim, players);
+        }
+
+        // Playout with the heuristic bot for everybody.
+        for _ in 0..MAX_PLAYOUT {
+            let (actor, legal) = match next_actor(rules, &sim, players) {
+                Some(turn) => turn,
+                None => break,
+            };
+            let action = playout_bot
+                .choose(&actor, &sim, players, &legal)
+                .unwrap_or_else(|| legal[0].clone());
+            if rules.apply(&actor, &mut sim, players, action).is_err() {
+                break;
+            }
+        }
+
+        let mut current = Some(node);
+        while let Some(i) = current {
+            nodes[i].visits += 1.0;
+            nodes[i].reward += if sim.durak == Some(nodes[i].actor) { 0.0 } else { 1.0 };
+            current = nodes[i].parent;
+        }
+    }
+    log_trace!(client = *me, nodes = nodes.len(); "Searched {} iterations.", iterations);
+    nodes[0]
+        .children
+        .iter()
+        .max_by(|&&a, &&b| nodes[a].visits.total_cmp(&nodes[b].visits))
+        .and_then(|&x| nodes[x].action.clone())
+}
+
+/// Deals the cards `me` cannot see at random. Cards a player is known to
+/// hold stay with them, the trump card stays at the bottom of the talon.
+pub fn determinize(state: &GameState, me: &ClientHash) -> GameState {
+    let mut seen: HashSet<Card> = HashSet::new();
+    if let Some(cards) = state.player_cards.get(me) {
+        seen.extend(cards.iter().cloned());
+    }
+    for (a, b) in &state.table_stacks {
+        seen.insert(a.clone());
+        seen.extend(b.iter().cloned());
+    }
+    seen.extend(state.discard.iter().cloned());
+    for (player, cards) in &state.revealed {
+        if player != me {
+            seen.extend(cards.iter().cloned());
+        }
+    }
+    let trump_card = state.card_stack.last().cloned();
+    seen.extend(trump_card.iter().cloned());
+
+    let mut pool: Vec<Card> = Card::deck().into_iter().filter(|x| !seen.contains(x)).collect();
+    thread_rng().shuffle(&mut pool);
+    let mut sim = state.clone();
+    let mut others: Vec<ClientHash> =
+        state.player_cards.keys().cloned().filter(|x| x != me).collect();
+    others.sort();
+    for player in others {
+        let count = state.hand_size(&player);
+        let mut hand = state.revealed.get(&player).cloned().unwrap_or_default();
+        while hand.len() < count {
+            match pool.pop() {
+                Some(card) => {
+                    hand.insert(card);
+                }
+                None => break,
+            }
+        }
+        sim.player_cards.insert(player, hand);
+    }
+    if let Some(card) = trump_card {
+        pool.truncate(state.talon_size() - 1);
+        pool.push(card);
+        sim.card_stack = pool;
+    }
+    sim.hidden_hands.clear();
+    sim.hidden_talon = 0;
+    sim
+}
+
+impl Node {
+    fn new(action: Option<GameAction>, actor: ClientHash, parent: Option<usize>) -> Node {
+        Node {
+            action,
+            actor,
+            parent,
+            children: Vec::new(),
+            visits: 0.0,
+            reward: 0.0,
+            available: 1.0,
+        }
+    }
+
+    /// Upper confidence bound of the node, infinite for nodes never tried
+    /// so that they are explored first.
+    fn ucb(&self) -> f64 {
+        if self.visits == 0.0 || self.available == 0.0 {
+            return f64::INFINITY;
+        }
+        self.reward / self.visits + EXPLORATION * (self.available.ln() / self.visits).sqrt()
+    }
+}
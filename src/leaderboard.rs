@@ -0,0 +1,222 @@
+//! Log of all finished games and the leaderboards computed from it. Every
+//! game is appended to a plain text file as one line:
+//!
+//! ```text
+//! 1760781234 podkidnoy-5 Kekse:1:a Gast:2 Kuchen:3:ad
+//! ```
+//!
+//! holding the end of the game in seconds since the Unix epoch, the rule
+//! variant and the finish position of every player. The flag `a` marks
+//! registered players, `d` the durak.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use network::*;
+use stats::*;
+
+// TODO import
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Players listed per leaderboard.
+pub const LEADERBOARD_SIZE: usize = 10;
+
+const DAY: u64 = 24 * 60 * 60;
+
+/// Games counted in a leaderboard. Days and weeks are calendar days and
+/// weeks starting on Monday, both in UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Period {
+    Daily,
+    Weekly,
+    #[default]
+    AllTime,
+}
+
+/// Best players among the games of a period, only registered players are
+/// listed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Leaderboard {
+    /// Variant the games were played in, `None` for all variants.
+    pub variant: Option<String>,
+    pub period: Period,
+    /// Ratings from the games of the period only, the best first.
+    pub rating: Vec<(String, f64)>,
+    /// Games not lost as durak, the most first.
+    pub wins: Vec<(String, usize)>,
+}
+
+/// Every finished game, appended to `path` if one is given.
+#[derive(Debug, Clone, Default)]
+pub struct ResultLog {
+    path: Option<PathBuf>,
+    results: Vec<GameResult>,
+}
+
+impl Period {
+    pub const ALL: &'static [Period] = &[Period::Daily, Period::Weekly, Period::AllTime];
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            Period::Daily => "daily",
+            Period::Weekly => "weekly",
+            Period::AllTime => "all-time",
+        }
+    }
+
+    /// Start of the period `now` lies in, both in seconds since the Unix
+    /// epoch.
+    pub fn start(&self, now: u64) -> u64 {
+        let day = now / DAY;
+        match *self {
+            Period::Daily => day * DAY,
+            // The epoch was a Thursday.
+            Period::Weekly => (day - (day + 3) % 7) * DAY,
+            Period::AllTime => 0,
+        }
+    }
+}
+
+impl ResultLog {
+    /// Results kept in memory only.
+    pub fn new() -> ResultLog {
+        ResultLog::default()
+    }
+
+    /// Loads the results stored at `path`. A missing file is created after
+    /// the first game.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<ResultLog> {
+        let path = path.into();
+        let mut results = Vec::new();
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    results.push(parse_result(&line)?);
+                }
+            }
+        }
+        Ok(ResultLog {
+            path: Some(path),
+            results,
+        })
+    }
+
+    pub fn results(&self) -> &[GameResult] {
+        &self.results
+    }
+
+    /// Adds a finished game to the end of the log.
+    pub fn append(&mut self, result: GameResult) -> Result<()> {
+        if let Some(ref path) = self.path {
+            let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+            writeln!(file, "{}", format_result(&result))?;
+        }
+        self.results.push(result);
+        Ok(())
+    }
+
+    /// Replays the games of `variant` in `period` to rank the players who
+    /// took part. `now` decides which day or week is meant.
+    pub fn leaderboard(&self, variant: Option<&str>, period: Period, now: u64) -> Leaderboard {
+        let start = period.start(now);
+        let mut stats = Stats::new();
+        for result in &self.results {
+            if result.time >= start && variant.is_none_or(|x| x == result.variant) {
+                // Statistics without a file cannot fail to save.
+                let _ = stats.record(result);
+            }
+        }
+        let mut rating: Vec<(String, f64)> =
+            stats.players().map(|x| (x.name.clone(), x.rating)).collect();
+        rating.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        rating.truncate(LEADERBOARD_SIZE);
+        let mut wins: Vec<(String, usize)> = stats
+            .players()
+            .map(|x| {
+                let total = x.total();
+                (x.name.clone(), total.games - total.durak)
+            })
+            .filter(|x| x.1 > 0)
+            .collect();
+        wins.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        wins.truncate(LEADERBOARD_SIZE);
+        Leaderboard {
+            variant: variant.map(String::from),
+            period,
+            rating,
+            wins,
+        }
+    }
+}
+
+/// Current time in seconds since the Unix epoch.
+pub fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|x| x.as_secs()).unwrap_or(0)
+}
+
+fn format_result(result: &GameResult) -> String {
+    let mut line = format!("{} {}", result.time, result.variant);
+    for placing in &result.placings {
+        line.push_str(&format!(" {}:{}", placing.name, placing.place));
+        if placing.registered || placing.durak {
+            line.push(':');
+            if placing.registered {
+                line.push('a');
+            }
+            if placing.durak {
+                line.push('d');
+            }
+        }
+    }
+    line
+}
+
+fn parse_result(line: &str) -> Result<GameResult> {
+    let mut parts = line.split_whitespace();
+    let time = parts.next().unwrap_or("").parse()?;
+    let variant = match parts.next() {
+        Some(variant) => variant.into(),
+        None => return Err(durak_error!(InvalidConfig, "Invalid result line.")),
+    };
+    let mut placings = Vec::new();
+    for part in parts {
+        let mut fields = part.split(':');
+        let name = fields.next().unwrap_or("");
+        let place = fields.next().unwrap_or("").parse()?;
+        let flags = fields.next().unwrap_or("");
+        placings.push(Placing {
+            name: name.into(),
+            place,
+            durak: flags.contains('d'),
+            registered: flags.contains('a'),
+        });
+    }
+    Ok(GameResult {
+        time,
+        variant,
+        placings,
+    })
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+impl FromStr for Period {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<Period> {
+        match Period::ALL.iter().find(|x| x.name() == s) {
+            Some(period) => Ok(*period),
+            None => Err(durak_error!(InvalidArgument, "Expected daily, weekly or all-time.")),
+        }
+    }
+}
@@ -1,12 +1,50 @@
+extern crate getopts;
+extern crate pbkdf2;
 extern crate rand;
+extern crate sha2;
+extern crate toml;
 
+#[macro_use]
+pub mod logging;
+pub mod account;
+pub mod bot;
+pub mod client;
+pub mod config;
+pub mod engine;
 pub mod network;
 pub mod game;
+pub mod i18n;
+pub mod leaderboard;
+pub mod ismcts;
 pub mod rules;
+pub mod simulate;
+pub mod stats;
+pub mod transport;
 
 #[cfg(test)]
 mod tests {
+    use account::*;
+    use bot::*;
+    use client::*;
+    use config::*;
     use game::*;
+    use i18n::*;
+    use ismcts::*;
+    use leaderboard::*;
+    use network::*;
+    use rules::*;
+    use simulate::*;
+    use stats::*;
+    use transport::*;
+    use std::env;
+    use std::fs;
+    use std::io::prelude::*;
+    use std::io::BufReader;
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::UnixStream;
+    use std::process;
+    use std::thread;
+    use std::time::Duration;
 
     #[test]
     fn card_compare_same_suite() {
@@ -110,4 +148,1278 @@ mod tests {
         assert_eq!(format!("{}", CardValue::King), "K");
         assert_eq!(format!("{}", CardValue::Ace), "A");
     }
+
+    #[test]
+    fn memory_transport() {
+        let (transport, connector) = MemoryTransport::new();
+        let mut server = Server::with_transport(transport, DefaultRules::new());
+        server.start().unwrap();
+        let (id, client) = connector.connect().unwrap();
+        client
+            .send(Command::Player(PlayerCommand::Name("Kekse".into())))
+            .unwrap();
+        client.send(Command::Player(PlayerCommand::List)).unwrap();
+        let mut answer = None;
+        while answer.is_none() {
+            assert!(server.poll().unwrap());
+            answer = client.recv_timeout(Duration::from_millis(1));
+        }
+        match answer {
+            Some(Answer::PlayerList(list)) => assert_eq!(list[&id].name, "Kekse"),
+            _ => panic!("expected player list"),
+        }
+    }
+
+    #[test]
+    fn admin_quit() {
+        let (transport, connector) = MemoryTransport::new();
+        let mut server = Server::with_transport(transport, DefaultRules::new());
+        let mut accounts = Accounts::new();
+        accounts.set_admin("admin", "geheim");
+        server.set_accounts(accounts);
+        server.start().unwrap();
+        let (_, client) = connector.connect().unwrap();
+        client.send(Command::Quit).unwrap();
+        let mut answer = None;
+        while answer.is_none() {
+            assert!(server.poll().unwrap());
+            answer = client.recv_timeout(Duration::from_millis(1));
+        }
+        match answer {
+            Some(Answer::Error(e)) => assert_eq!(e.code(), ErrorCode::NotAdmin),
+            _ => panic!("server shut down by a player"),
+        }
+        let login = Command::parse("player login admin falsch").unwrap();
+        client.send(login).unwrap();
+        client.send(Command::parse("player login admin geheim").unwrap()).unwrap();
+        client.send(Command::Quit).unwrap();
+        let mut polls = 0;
+        while server.poll().unwrap() {
+            polls += 1;
+            assert!(polls < 1000, "admin could not shut down");
+        }
+    }
+
+    #[test]
+    fn hidden_hands() {
+        let (transport, connector) = MemoryTransport::new();
+        let mut server = Server::with_transport(transport, DefaultRules::new());
+        server.start().unwrap();
+        let (first, kekse) = connector.connect().unwrap();
+        let (second, kuchen) = connector.connect().unwrap();
+        let (_, torte) = connector.connect().unwrap();
+        for (client, name) in [(&kekse, "Kekse"), (&kuchen, "Kuchen"), (&torte, "Torte")] {
+            client.send(Command::parse(format!("player name {}", name)).unwrap()).unwrap();
+        }
+        kekse.send(Command::parse("table new Tisch").unwrap()).unwrap();
+        kekse.send(Command::Player(PlayerCommand::State)).unwrap();
+        let tablehash = match receive(&mut server, &kekse, "player state") {
+            Answer::PlayerState(_, player) => player.table.unwrap(),
+            _ => unreachable!(),
+        };
+        kuchen.send(Command::Table(TableCommand::Join(tablehash, None))).unwrap();
+        torte.send(Command::Table(TableCommand::Watch(tablehash, None))).unwrap();
+        server.poll().unwrap();
+        kekse.send(Command::parse("game start").unwrap()).unwrap();
+        // Everybody only sees the own hand, the others are counted.
+        let state = match receive(&mut server, &kekse, "game state") {
+            Answer::GameState(state) => state,
+            _ => unreachable!(),
+        };
+        assert_eq!(state.player_cards[&first].len(), 5);
+        assert!(state.player_cards[&second].is_empty());
+        assert_eq!(state.hand_size(&second), 5);
+        assert_eq!(state.talon_size(), 26);
+        assert_eq!(state.card_stack.len(), 1);
+        match receive(&mut server, &kuchen, "game state") {
+            Answer::GameState(state) => {
+                assert!(state.player_cards[&first].is_empty());
+                assert_eq!(state.player_cards[&second].len(), 5);
+            }
+            _ => unreachable!(),
+        }
+        match receive(&mut server, &torte, "game state") {
+            Answer::GameState(state) => {
+                assert!(state.player_cards.values().all(|x| x.is_empty()));
+                assert_eq!(state.hand_size(&first) + state.hand_size(&second), 10);
+            }
+            _ => unreachable!(),
+        }
+        // Spectators may ask for the state again, still without any hand.
+        torte.send(Command::parse("game state").unwrap()).unwrap();
+        match receive(&mut server, &torte, "game state") {
+            Answer::GameState(state) => {
+                assert!(state.player_cards.values().all(|x| x.is_empty()));
+                assert_eq!(state.talon_size(), 26);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn unix_socket() {
+        let path = env::temp_dir().join(format!("durak-test-{}.sock", process::id()));
+        let mut server = Server::new_unix(&path, Some(0o600), DefaultRules::new()).unwrap();
+        server.start().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        let stream = UnixStream::connect(&path).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_millis(1)))
+            .unwrap();
+        (&stream).write_all(b"player name Kekse\nplayer state\n").unwrap();
+        let mut reader = BufReader::new(&stream);
+        let mut lines = Vec::new();
+        while lines.len() < 2 {
+            assert!(server.poll().unwrap());
+            let mut line = String::new();
+            if reader.read_line(&mut line).is_ok() && !line.is_empty() {
+                lines.push(line);
+            }
+        }
+        assert!(lines[0].starts_with("hash  "));
+        assert_eq!(lines[1], "name  Kekse\n");
+        drop(server);
+        assert!(!path.exists());
+
+        // Other files are never replaced by the socket.
+        fs::write(&path, "Kekse").unwrap();
+        assert!(UnixTransport::bind(&path).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "Kekse");
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn config_args() {
+        let config = Config::from_args(
+            vec!["--bind", "127.0.0.1:2342", "--max-tables", "3", "--cards", "6"]
+                .into_iter()
+                .map(String::from),
+        ).unwrap();
+        assert_eq!(config.addresses(), vec!["127.0.0.1:2342".to_string()]);
+        assert_eq!(config.room.max_tables, Some(3));
+        assert_eq!(config.cards_per_player, 6);
+        assert!(Config::from_args(vec!["--cards".to_string(), "0".to_string()]).is_err());
+        assert!(Config::from_args(vec!["-l".to_string(), "loud".to_string()]).is_err());
+    }
+
+    #[test]
+    fn unix_mode() {
+        let path = env::temp_dir().join(format!("durak-config-{}.toml", process::id()));
+        let mode = |value: &str| {
+            fs::write(&path, format!("[server]\nunix_mode = {}\n", value)).unwrap();
+            Config::load(path.clone()).map(|x| x.unix_mode)
+        };
+        assert_eq!(mode("\"660\"").unwrap(), Some(0o660));
+        assert_eq!(mode("\"0o600\"").unwrap(), Some(0o600));
+        // Integers would be read as decimal.
+        assert_eq!(mode("660").unwrap_err().code(), ErrorCode::InvalidConfig);
+        assert_eq!(mode("0o660").unwrap_err().code(), ErrorCode::InvalidConfig);
+        assert!(mode("\"698\"").is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn maximum_cards() {
+        let cards = |n: &str| Config::from_args(vec!["--cards".to_string(), n.to_string()]);
+        assert!(cards("18").is_err());
+        assert_eq!(cards("17").unwrap().cards_per_player, 17);
+
+        // The last card of the talon still decides the trump.
+        let rules = DefaultRules::with_cards_per_player(17).seeded(1);
+        let players = [1, 2];
+        let mut state = GameState::new();
+        let dealt = rules.apply(&1, &mut state, &players, GameAction::DealCards).unwrap();
+        assert_eq!(dealt.card_stack.len(), 2);
+        assert!(dealt.trump.is_some());
+        let attacker = dealt.attack_player.unwrap();
+        let defender = dealt.target_player.unwrap();
+        let defended = rules.legal_actions(&attacker, &dealt, &players).into_iter().any(|attack| {
+            let mut state = dealt.clone();
+            let attacked = rules.apply(&attacker, &mut state, &players, attack).unwrap();
+            let defense = rules
+                .legal_actions(&defender, &attacked, &players)
+                .into_iter()
+                .find(|x| matches!(*x, GameAction::PutCard(_, Some(0))));
+            match defense {
+                Some(defense) => rules.apply(&defender, &mut state, &players, defense).is_ok(),
+                None => false,
+            }
+        });
+        assert!(defended);
+    }
+
+    #[test]
+    fn error_codes() {
+        for code in ErrorCode::ALL {
+            assert_eq!(code.name().parse::<ErrorCode>().unwrap(), *code);
+        }
+        let error = "X1".parse::<Card>().unwrap_err();
+        assert_eq!(error.code(), ErrorCode::InvalidArgument);
+        assert_eq!(error.error_type(), DurakErrorType::ParserError);
+        let mut buffer = Vec::new();
+        write_answer::<DefaultRules, _>(&mut buffer, 0, Answer::Error(error)).unwrap();
+        assert_eq!(
+            String::from_utf8(buffer).unwrap(),
+            "ERROR INVALID_ARGUMENT Invalid card value specified.\n"
+        );
+    }
+
+    #[test]
+    fn localized_errors() {
+        let error = Command::<DefaultRules>::parse("spiel").unwrap_err();
+        assert_eq!(error.to_string(), "Unknown command spiel.");
+        assert_eq!(
+            error.clone().localized(Language::German).to_string(),
+            "Unbekannter Befehl spiel."
+        );
+        assert_eq!(
+            error.localized(Language::Russian).to_string(),
+            "Неизвестная команда spiel."
+        );
+        assert_eq!("ru".parse::<Language>().unwrap(), Language::Russian);
+        let error = "xx".parse::<Language>().unwrap_err();
+        assert_eq!(error.to_string(), "Unknown language xx.");
+        assert_eq!(error.localized(Language::German).to_string(), "Unbekannte Sprache xx.");
+        let error = validate_name("").unwrap_err();
+        assert_eq!(
+            error.localized(Language::Russian).to_string(),
+            "Имя должно содержать от 1 до 24 букв, цифр, точек, дефисов или подчёркиваний."
+        );
+        let args = ["Kekse".to_string(), "Kuchen".to_string()];
+        assert_eq!(
+            describe(Language::German, EventMessage::Renamed, &args),
+            "Kekse heißt jetzt Kuchen."
+        );
+    }
+
+    #[test]
+    fn command_display() {
+        for line in &[
+            "player name Kekse",
+            "player lang de",
+            "player register Kekse geheim",
+            "player login Kekse geheim",
+            "player stats",
+            "player stats Kekse",
+            "leaderboard weekly",
+            "leaderboard podkidnoy-5 daily",
+            "table join 00000000000000FF",
+            "table join 00000000000000FF geheim",
+            "table new Kuchen password=geheim",
+            "table new Kuchen private",
+            "table new Kuchen min=3 max=4",
+            "table settings max 4",
+            "table settings password off",
+            "table settings timer defend 30",
+            "table settings timer throw-in off",
+            "table settings abandon durak",
+            "table chat Hallo Welt",
+            "game put A2 1",
+            "game put 03",
+            "game take",
+            "game pass",
+            "game ready",
+            "game unready",
+            "table addbot random",
+            "table watch 00000000000000FF",
+            "table settings spectators off",
+            "table settings name Kuchen",
+            "table kick 0000000000000001",
+            "table unlock",
+            "table close",
+            "table match",
+            "table match games 3",
+            "table match score 2",
+            "table match off",
+        ] {
+            let command = Command::<DefaultRules>::parse(*line).unwrap();
+            assert_eq!(command.to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn client() {
+        let path = env::temp_dir().join(format!("durak-client-{}.sock", process::id()));
+        let mut server = Server::new_unix(&path, None, DefaultRules::new()).unwrap();
+        server.start().unwrap();
+        let mut client = DurakClient::connect_unix(&path).unwrap();
+        client.set_name("Kekse").unwrap();
+        let options = TableOptions::default();
+        client.send_table(TableCommand::New("Kuchen mit Sahne".into(), options)).unwrap();
+        client.send_table(TableCommand::List).unwrap();
+        client.send_player(PlayerCommand::State).unwrap();
+        let mut events = Vec::new();
+        while events.len() < 3 {
+            assert!(server.poll().unwrap());
+            if let Some(event) = client.next_event(Duration::from_millis(1)) {
+                events.push(event);
+            }
+        }
+        let table = match events[0] {
+            Event::TableSettings(ref settings) => {
+                assert_eq!(settings.name, "Kuchen mit Sahne");
+                assert_eq!(settings.players, 1);
+                settings.hash
+            }
+            ref event => panic!("unexpected {:?}", event),
+        };
+        match events[1] {
+            Event::TableList(ref tables) => {
+                assert_eq!(tables.len(), 1);
+                assert_eq!(tables[0].hash, table);
+                assert_eq!(tables[0].state, TableState::Idle);
+                assert_eq!(tables[0].name, "Kuchen mit Sahne");
+                assert_eq!(tables[0].access, "open");
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+        match events[2] {
+            Event::PlayerState(ref player) => {
+                assert_eq!(player.name, "Kekse");
+                assert_eq!(player.lang, Language::English);
+                assert_eq!(player.table, Some(table));
+            }
+            ref event => panic!("unexpected {:?}", event),
+        }
+    }
+
+    #[test]
+    fn bot_game() {
+        let rules = DefaultRules::new();
+        let players = vec![1, 2, 3];
+        let budget = Duration::from_millis(20);
+        let config = BotConfig::default();
+        let mut bots: Vec<Box<dyn Bot>> = vec![
+            BotLevel::Random.create(&rules, &config).unwrap(),
+            BotLevel::Heuristic.create(&rules, &config).unwrap(),
+            Box::new(IsmctsBot::new(rules.clone(), budget).blocking()),
+        ];
+        let mut state = GameState::new();
+        rules.apply(&1, &mut state, &players, GameAction::DealCards).unwrap();
+        let mut moves = 0;
+        while !state.game_over {
+            let snapshot = state.clone();
+            for (player, bot) in players.iter().zip(bots.iter_mut()) {
+                let actions = rules.legal_actions(player, &state, &players);
+                if let Some(action) = bot.choose(player, &state, &players, &actions) {
+                    rules.apply(player, &mut state, &players, action).unwrap();
+                    break;
+                }
+            }
+            let cards = state.player_cards.values().map(|x| x.len()).sum::<usize>() +
+                state.table_stacks.iter().map(|x| 1 + x.1.iter().count()).sum::<usize>() +
+                state.card_stack.len() + state.discard.len();
+            assert_eq!(cards, 36);
+            assert!(state.game_over || snapshot.table_stacks != state.table_stacks ||
+                snapshot.passed != state.passed || snapshot.card_stack != state.card_stack);
+            moves += 1;
+            assert!(moves < 1000);
+        }
+        assert_eq!(state.finished.len() + state.durak.iter().count(), 3);
+    }
+
+    #[test]
+    fn add_bot() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::Player(PlayerCommand::Name("Kekse".into())));
+        new_table(&mut room, 1, "table new Kuchen");
+        let command = Command::parse("table addbot").unwrap();
+        assert!(room.handle_command(&1, command).is_none());
+        let mut answer = room.handle_command(&1, Command::parse("game start").unwrap());
+        let rules = DefaultRules::new();
+        for _ in 0..1000 {
+            let state = match answer {
+                Some((AnswerTarget::List(ref targets), Answer::GameState(ref state))) => {
+                    assert_eq!(targets.len(), 2);
+                    state.clone()
+                }
+                _ => panic!("no game state"),
+            };
+            if state.game_over {
+                return;
+            }
+            let players: Vec<ClientHash> = state.player_cards.keys().cloned().collect();
+            // The bot moves immediately, so the human always has something to do.
+            let action = rules.legal_actions(&1, &state, &players).remove(0);
+            answer = room.handle_command(&1, Command::Game(GameCommand::Action(action)));
+        }
+        panic!("game did not finish");
+    }
+
+    #[test]
+    fn spectators() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        room.handle_command(&2, Command::parse("player name Zuschauer").unwrap());
+        let table = new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        let watch = Command::parse(format!("table watch {:016X}", table)).unwrap();
+        assert!(room.handle_command(&2, watch.clone()).is_none());
+        let state = match room.handle_command(&1, Command::parse("game start").unwrap()) {
+            Some((AnswerTarget::List(targets), Answer::GameState(state))) => {
+                assert_eq!(targets.len(), 3);
+                assert_eq!(targets[2], 2);
+                state
+            }
+            _ => panic!("no game state"),
+        };
+        let mut view = Vec::new();
+        write_game_state(&mut view, 2, &state).unwrap();
+        let view = String::from_utf8(view).unwrap();
+        assert!(view.starts_with("trump "));
+        assert!(view.contains("talon "));
+        match room.handle_command(&2, Command::parse("table chat hallo").unwrap()) {
+            Some((AnswerTarget::List(targets), Answer::Chat(2, _))) => assert_eq!(targets.len(), 3),
+            _ => panic!("no chat"),
+        }
+
+        match room.handle_command(&2, Command::parse("table settings spectators off").unwrap()) {
+            Some((_, Answer::Error(e))) => assert_eq!(e.code(), ErrorCode::NoTableJoined),
+            _ => panic!("spectator changed settings"),
+        }
+        let command = Command::parse("table settings spectators off").unwrap();
+        assert!(room.handle_command(&1, command).is_none());
+        match room.handle_command(&2, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => assert_eq!(player.watching, None),
+            _ => panic!("no player state"),
+        }
+        match room.handle_command(&2, watch) {
+            Some((_, Answer::Error(e))) => assert_eq!(e.code(), ErrorCode::SpectatingDisabled),
+            _ => panic!("watched despite disabled spectators"),
+        }
+    }
+
+    #[test]
+    fn table_owner() {
+        let mut room = Room::new(DefaultRules::new());
+        for client in 1..4 {
+            let command = Command::parse(format!("player name Spieler{}", client)).unwrap();
+            room.handle_command(&client, command);
+        }
+        let table = new_table(&mut room, 1, "table new Kuchen");
+        let error = |answer: Option<(AnswerTarget, Answer<DefaultRules>)>| match answer {
+            Some((_, Answer::Error(e))) => e.code(),
+            _ => panic!("no error"),
+        };
+        room.handle_command(&2, Command::Table(TableCommand::Join(table, None)));
+        let start = Command::parse("game start").unwrap();
+        assert_eq!(error(room.handle_command(&2, start)), ErrorCode::NotTableOwner);
+        let kick = Command::parse("table kick 0000000000000001").unwrap();
+        assert_eq!(error(room.handle_command(&2, kick)), ErrorCode::NotTableOwner);
+        let addbot = Command::parse("table addbot").unwrap();
+        assert_eq!(error(room.handle_command(&2, addbot.clone())), ErrorCode::NotTableOwner);
+
+        assert!(room.handle_command(&1, Command::parse("table lock").unwrap()).is_none());
+        let join = Command::Table(TableCommand::Join(table, None));
+        assert_eq!(error(room.handle_command(&3, join.clone())), ErrorCode::TableLocked);
+        assert_eq!(error(room.handle_command(&1, addbot)), ErrorCode::TableLocked);
+        room.handle_command(&1, Command::parse("table unlock").unwrap());
+        assert!(room.handle_command(&3, join).is_none());
+        let kick = Command::parse("table kick 0000000000000002").unwrap();
+        match room.handle_command(&1, kick) {
+            Some((AnswerTarget::List(targets), Answer::Kicked(kicked))) => {
+                assert_eq!(targets, vec![2]);
+                assert_eq!(kicked, table);
+            }
+            _ => panic!("kicked player not told"),
+        }
+        let join = Command::Table(TableCommand::Join(table, None));
+        assert_eq!(error(room.handle_command(&2, join)), ErrorCode::Banned);
+
+        room.handle_command(&1, Command::parse("table leave").unwrap());
+        match room.handle_command(&3, Command::parse("table settings").unwrap()) {
+            Some((_, Answer::TableSettings(_, settings))) => {
+                assert_eq!(settings.owner, Some(3));
+                assert_eq!(settings.players, vec![3]);
+            }
+            _ => panic!("no settings"),
+        }
+        match room.handle_command(&3, Command::parse("table close").unwrap()) {
+            Some((AnswerTarget::List(targets), Answer::Error(_))) => assert_eq!(targets, vec![3]),
+            _ => panic!("table not closed"),
+        }
+        match room.handle_command(&3, Command::Table(TableCommand::List)) {
+            Some((_, Answer::TableList(tables))) => assert!(tables.is_empty()),
+            _ => panic!("no table list"),
+        }
+    }
+
+    #[test]
+    fn player_limits() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        let error = |answer: Option<(AnswerTarget, Answer<DefaultRules>)>| match answer {
+            Some((_, Answer::Error(e))) => e.code(),
+            _ => panic!("no error"),
+        };
+        let command = Command::parse("table new Kuchen max=8").unwrap();
+        assert_eq!(error(room.handle_command(&1, command)), ErrorCode::InvalidPlayerLimits);
+        new_table(&mut room, 1, "table new Kuchen min=3 max=4");
+        room.handle_command(&1, Command::parse("table settings max 7").unwrap());
+        let command = Command::parse("table settings min 1").unwrap();
+        assert_eq!(error(room.handle_command(&1, command)), ErrorCode::InvalidPlayerLimits);
+        match room.handle_command(&1, Command::parse("table settings").unwrap()) {
+            Some((_, Answer::TableSettings(_, table))) => {
+                assert_eq!((table.min_players, table.max_players), (3, 7));
+            }
+            _ => panic!("no settings"),
+        }
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        let start = Command::parse("game start").unwrap();
+        assert_eq!(error(room.handle_command(&1, start)), ErrorCode::NotEnoughPlayers);
+        assert_eq!(Table::new("Torte", DefaultRules::with_cards_per_player(17)).max_players, 2);
+    }
+
+    #[test]
+    fn full_table() {
+        // Six cards each leave room for five players and a trump card.
+        let mut room = Room::new(DefaultRules::with_cards_per_player(6));
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        match room.handle_command(&1, Command::parse("table new Kuchen").unwrap()) {
+            Some((_, Answer::TableSettings(_, table))) => assert_eq!(table.max_players, 5),
+            _ => panic!("no table created"),
+        }
+        for _ in 0..4 {
+            room.handle_command(&1, Command::parse("table addbot").unwrap());
+        }
+        match room.handle_command(&1, Command::parse("table addbot").unwrap()) {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => {
+                assert_eq!(e.code(), ErrorCode::TableFull)
+            }
+            _ => panic!("sixth player seated"),
+        }
+        match room.handle_command(&1, Command::parse("game start").unwrap()) {
+            Some((_, Answer::GameState(state))) => {
+                assert_eq!(state.player_cards.len(), 5);
+                assert_eq!(state.card_stack.len(), 6);
+                assert!(state.trump.is_some());
+            }
+            _ => panic!("game not started"),
+        }
+    }
+
+    #[test]
+    fn private_tables() {
+        let mut room = Room::new(DefaultRules::new());
+        for client in 1..3 {
+            let command = Command::parse(format!("player name Spieler{}", client)).unwrap();
+            room.handle_command(&client, command);
+        }
+        let tables = |room: &mut Room<DefaultRules>, client| {
+            match room.handle_command(&client, Command::Table(TableCommand::List)) {
+                Some((_, Answer::TableList(tables))) => tables,
+                _ => panic!("no table list"),
+            }
+        };
+        let error = |answer: Option<(AnswerTarget, Answer<DefaultRules>)>| match answer {
+            Some((_, Answer::Error(e))) => e.code(),
+            _ => panic!("no error"),
+        };
+
+        let table = new_table(&mut room, 1, "table new Kuchen password=geheim");
+        assert_eq!(tables(&mut room, 2).len(), 1);
+        let join = Command::Table(TableCommand::Join(table, None));
+        assert_eq!(error(room.handle_command(&2, join)), ErrorCode::WrongPassword);
+        let join = Command::Table(TableCommand::Join(table, Some("geheim".into())));
+        assert!(room.handle_command(&2, join).is_none());
+        room.handle_command(&1, Command::parse("table close").unwrap());
+
+        let table = new_table(&mut room, 1, "table new Torte private");
+        assert!(tables(&mut room, 2).is_empty());
+        let invite = match room.handle_command(&1, Command::parse("table settings").unwrap()) {
+            Some((_, Answer::TableSettings(_, settings))) => settings.invite.unwrap(),
+            _ => panic!("no settings"),
+        };
+        let watch = Command::Table(TableCommand::Watch(table, None));
+        assert_eq!(error(room.handle_command(&2, watch)), ErrorCode::WrongPassword);
+        let join = Command::Table(TableCommand::Join(table, Some(invite)));
+        assert!(room.handle_command(&2, join).is_none());
+        assert_eq!(tables(&mut room, 2).len(), 1);
+    }
+
+    #[test]
+    fn table_expiry() {
+        let mut room = Room::new(DefaultRules::new());
+        room.set_config(RoomConfig {
+            empty_table_timeout: Some(Duration::from_secs(0)),
+            idle_table_timeout: Some(Duration::from_secs(3600)),
+            ..RoomConfig::default()
+        });
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        assert!(room.tick().is_empty());
+        room.handle_command(&1, Command::parse("table leave").unwrap());
+        match room.tick().pop() {
+            Some((AnswerTarget::List(targets), Answer::Error(e))) => {
+                assert_eq!(e.code(), ErrorCode::TableNotFound);
+                assert_eq!(targets.len(), 1);
+            }
+            _ => panic!("empty table not deleted"),
+        }
+        match room.handle_command(&1, Command::Player(PlayerCommand::List)) {
+            Some((_, Answer::PlayerList(players))) => assert_eq!(players.len(), 1),
+            _ => panic!("no player list"),
+        }
+
+        room.set_config(RoomConfig {
+            idle_table_timeout: Some(Duration::from_secs(0)),
+            ..RoomConfig::default()
+        });
+        new_table(&mut room, 1, "table new Torte");
+        assert_eq!(room.tick().len(), 1);
+        match room.handle_command(&1, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => assert_eq!(player.table, None),
+            _ => panic!("no player state"),
+        }
+    }
+
+    #[test]
+    fn ready_check() {
+        let mut room = Room::new(DefaultRules::new());
+        room.set_config(RoomConfig {
+            start_countdown: Some(Duration::from_secs(0)),
+            ..RoomConfig::default()
+        });
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        assert!(room.tick().is_empty());
+        match room.handle_command(&1, Command::parse("game ready").unwrap()) {
+            Some((AnswerTarget::List(_), Answer::Ready(players))) => assert_eq!(players.len(), 2),
+            _ => panic!("no ready list"),
+        }
+        match room.tick().pop() {
+            Some((AnswerTarget::List(targets), Answer::Countdown(Some(0)))) => {
+                assert_eq!(targets.len(), 2)
+            }
+            _ => panic!("no countdown"),
+        }
+        match room.tick().pop() {
+            Some((AnswerTarget::List(_), Answer::GameState(state))) => assert!(!state.game_over),
+            _ => panic!("game not started"),
+        }
+        let command = Command::parse("game ready").unwrap();
+        match room.handle_command(&1, command) {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => {
+                assert_eq!(e.code(), ErrorCode::GameRunning)
+            }
+            _ => panic!("ready during game"),
+        }
+    }
+
+    #[test]
+    fn match_mode() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        let command = Command::parse("table match").unwrap();
+        match room.handle_command(&1, command) {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => {
+                assert_eq!(e.code(), ErrorCode::NoMatch)
+            }
+            _ => panic!("match without setting one up"),
+        }
+        match room.handle_command(&1, Command::parse("table match games 1").unwrap()) {
+            Some((AnswerTarget::List(_), Answer::MatchSummary(_, series))) => {
+                assert_eq!(series.standings().len(), 2);
+                assert!(!series.is_over());
+            }
+            _ => panic!("no match summary"),
+        }
+        let rules = DefaultRules::new();
+        let mut answer = room.handle_command(&1, Command::parse("game start").unwrap());
+        let durak = loop {
+            let state = match answer {
+                Some((_, Answer::GameState(ref state))) => state.clone(),
+                _ => panic!("no game state"),
+            };
+            if state.game_over {
+                break state.durak;
+            }
+            let players: Vec<ClientHash> = state.player_cards.keys().cloned().collect();
+            let action = rules.legal_actions(&1, &state, &players).remove(0);
+            answer = room.handle_command(&1, Command::Game(GameCommand::Action(action)));
+        };
+        match room.tick().pop() {
+            Some((AnswerTarget::List(_), Answer::MatchSummary(_, series))) => {
+                assert!(series.is_over());
+                assert_eq!(series.played, 1);
+                assert_eq!(series.last_durak, durak);
+            }
+            _ => panic!("match result not announced"),
+        }
+        assert!(room.tick().is_empty());
+        // Rematch, started by the durak of the last game.
+        match room.handle_command(&1, Command::parse("game start").unwrap()) {
+            Some((_, Answer::GameState(state))) => {
+                if durak.is_some() {
+                    assert_eq!(state.attack_player, durak);
+                }
+            }
+            _ => panic!("rematch not started"),
+        }
+    }
+
+    #[test]
+    fn move_timers() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        for line in &["timer attack 0", "timer defend 0", "timer throw-in 0"] {
+            let command = Command::parse(format!("table settings {}", line)).unwrap();
+            assert!(room.handle_command(&1, command).is_none());
+        }
+        room.handle_command(&1, Command::parse("game start").unwrap());
+        // The human never moves, the timers play for them until the game ends.
+        let mut timers = 0;
+        for _ in 0..10000 {
+            for (_, answer) in room.tick() {
+                match answer {
+                    Answer::MoveTimer(_, players) => {
+                        assert_eq!(players, vec![1]);
+                        timers += 1;
+                    }
+                    Answer::GameState(ref state) if state.game_over => {
+                        assert!(timers > 0);
+                        return;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        panic!("game did not finish");
+    }
+
+    #[test]
+    fn abandonment() {
+        let mut room = Room::new(DefaultRules::new());
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        room.handle_command(&2, Command::parse("player name Kuchen").unwrap());
+        let tablehash = new_table(&mut room, 1, "table new Torte");
+        let join = format!("table join {:016X}", tablehash);
+        room.handle_command(&2, Command::parse(join).unwrap());
+        room.handle_command(&1, Command::parse("game start").unwrap());
+        // A bot plays on for a player who leaves.
+        match room.handle_command(&2, Command::parse("table leave").unwrap()) {
+            Some((AnswerTarget::List(targets), Answer::GameState(state))) => {
+                assert_eq!(targets[0], 1);
+                assert!(!state.player_cards.contains_key(&2));
+                assert_eq!(state.player_cards.len(), 2);
+            }
+            _ => panic!("no bot took over"),
+        }
+        match room.handle_command(&2, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => {
+                assert_eq!(player.table, None);
+                assert_eq!(player.abandoned, 1);
+            }
+            _ => panic!("no player state"),
+        }
+
+        // Or the game ends with them as durak, remembered across connections.
+        let tablehash = new_table(&mut room, 2, "table new Kuchen");
+        let command = Command::parse("table settings abandon durak").unwrap();
+        assert!(room.handle_command(&2, command).is_none());
+        let join = format!("table join {:016X}", tablehash);
+        room.handle_command(&3, Command::parse("player register Torte geheim").unwrap());
+        room.handle_command(&3, Command::parse(join).unwrap());
+        room.handle_command(&2, Command::parse("game start").unwrap());
+        match room.disconnect(&3).pop() {
+            Some((AnswerTarget::List(targets), Answer::GameState(state))) => {
+                assert_eq!(targets, vec![2]);
+                assert!(state.game_over);
+                assert_eq!(state.durak, Some(3));
+            }
+            _ => panic!("game not ended"),
+        }
+        // Accounts keep the count in their statistics, guests do not.
+        room.handle_command(&4, Command::parse("player login Torte geheim").unwrap());
+        match room.handle_command(&4, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => assert_eq!(player.abandoned, 1),
+            _ => panic!("no player state"),
+        }
+        match room.handle_command(&4, Command::parse("player stats").unwrap()) {
+            Some((_, Answer::PlayerStats(stats))) => assert_eq!(stats.abandoned, 1),
+            _ => panic!("no statistics"),
+        }
+        room.disconnect(&2);
+        room.handle_command(&5, Command::parse("player name Kuchen").unwrap());
+        match room.handle_command(&5, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => assert_eq!(player.abandoned, 0),
+            _ => panic!("no player state"),
+        }
+    }
+
+    #[test]
+    fn passive_timeouts() {
+        let rules = DefaultRules::new();
+        for _ in 0..100 {
+            let mut room = Room::new(DefaultRules::new());
+            room.set_config(RoomConfig {
+                max_timeouts: Some(1),
+                ..RoomConfig::default()
+            });
+            for &(client, name) in &[(1, "Kekse"), (2, "Kuchen"), (3, "Torte")] {
+                let command = Command::parse(format!("player name {}", name)).unwrap();
+                room.handle_command(&client, command);
+            }
+            let tablehash = new_table(&mut room, 1, "table new Torte");
+            for client in 2..4 {
+                let join = format!("table join {:016X}", tablehash);
+                room.handle_command(&client, Command::parse(join).unwrap());
+            }
+            for line in &["timer attack off", "timer defend off", "timer throw-in 0"] {
+                let command = Command::parse(format!("table settings {}", line)).unwrap();
+                assert!(room.handle_command(&1, command).is_none());
+            }
+            let state = match room.handle_command(&1, Command::parse("game start").unwrap()) {
+                Some((_, Answer::GameState(state))) => state,
+                _ => panic!("game not started"),
+            };
+            let players: Vec<ClientHash> = state.player_cards.keys().cloned().collect();
+            let attacker = state.attack_player.unwrap();
+            let defender = state.target_player.unwrap();
+            let neighbor = state.neighbor_player.unwrap();
+            let attack = rules.legal_actions(&attacker, &state, &players).remove(0);
+            let command = Command::Game(GameCommand::Action(attack));
+            let state = match room.handle_command(&attacker, command) {
+                Some((_, Answer::GameState(state))) => state,
+                _ => panic!("attack failed"),
+            };
+            // Deals where the defender cannot beat the attack are retried.
+            let defense = rules
+                .legal_actions(&defender, &state, &players)
+                .into_iter()
+                .find(|x| matches!(*x, GameAction::PutCard(_, Some(_))));
+            let defense = match defense {
+                Some(defense) => defense,
+                None => continue,
+            };
+            room.handle_command(&defender, Command::Game(GameCommand::Action(defense)));
+            room.handle_command(&attacker, Command::Game(GameCommand::Action(GameAction::Pass)));
+            // Only the neighbour may still throw in, letting that time out
+            // costs no seat.
+            let mut passed = false;
+            for _ in 0..100 {
+                for (_, answer) in room.tick() {
+                    match answer {
+                        Answer::Error(e) => panic!("unexpected error {}", e),
+                        Answer::GameState(state) => {
+                            assert!(state.player_cards.contains_key(&neighbor));
+                            passed = true;
+                        }
+                        _ => {}
+                    }
+                }
+                if passed {
+                    break;
+                }
+            }
+            assert!(passed);
+            match room.handle_command(&neighbor, Command::Player(PlayerCommand::State)) {
+                Some((_, Answer::PlayerState(_, player))) => {
+                    assert_eq!(player.table, Some(tablehash));
+                    assert_eq!(player.abandoned, 0);
+                }
+                _ => panic!("no player state"),
+            }
+            return;
+        }
+        panic!("the defender never could defend");
+    }
+
+    #[test]
+    fn accounts() {
+        let path = env::temp_dir().join(format!("durak-accounts-{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+        let mut accounts = Accounts::open(path.clone()).unwrap();
+        accounts.reserve("admin");
+        let mut room = Room::new(DefaultRules::new());
+        room.set_accounts(accounts);
+        let error = |answer: Option<(AnswerTarget, Answer<DefaultRules>)>| match answer {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => e.code(),
+            _ => panic!("no error"),
+        };
+        let command = Command::parse("player register Kekse geheim").unwrap();
+        assert!(room.handle_command(&1, command).is_none());
+        room.disconnect(&1);
+        let command = Command::parse("player register Kekse anders").unwrap();
+        assert_eq!(error(room.handle_command(&2, command)), ErrorCode::NameReserved);
+        let command = Command::parse("player name admin").unwrap();
+        assert_eq!(error(room.handle_command(&2, command)), ErrorCode::NameReserved);
+        let command = Command::parse("player name Kekse").unwrap();
+        assert_eq!(error(room.handle_command(&2, command)), ErrorCode::NameReserved);
+        let command = Command::parse("player login Kekse anders").unwrap();
+        assert_eq!(error(room.handle_command(&2, command)), ErrorCode::WrongPassword);
+
+        // The password survives a restart, but not in plain text.
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with("Kekse ") && !content.contains("geheim"));
+        let mut room = Room::new(DefaultRules::new());
+        room.set_accounts(Accounts::open(path.clone()).unwrap());
+        let command = Command::parse("player login Kekse geheim").unwrap();
+        assert!(room.handle_command(&2, command).is_none());
+        match room.handle_command(&2, Command::Player(PlayerCommand::State)) {
+            Some((_, Answer::PlayerState(_, player))) => {
+                assert_eq!(player.name, "Kekse");
+                assert!(player.registered);
+            }
+            _ => panic!("no player state"),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn password_throttle() {
+        let mut accounts = Accounts::new();
+        accounts.set_admin("admin", "geheim");
+        let mut room = Room::new(DefaultRules::new());
+        room.set_accounts(accounts);
+        let login = |room: &mut Room<DefaultRules>, client, password| {
+            let command = Command::parse(format!("player login admin {}", password)).unwrap();
+            match room.handle_command(&client, command) {
+                Some((AnswerTarget::Direct, Answer::Error(e))) => Some(e.code()),
+                _ => None,
+            }
+        };
+        for _ in 0..3 {
+            assert_eq!(login(&mut room, 1, "falsch"), Some(ErrorCode::WrongPassword));
+        }
+        // Further attempts are refused without checking the password.
+        assert_eq!(login(&mut room, 1, "geheim"), Some(ErrorCode::TooManyAttempts));
+        let command = Command::parse("player register Kekse geheim").unwrap();
+        match room.handle_command(&1, command) {
+            Some((_, Answer::Error(e))) => assert_eq!(e.code(), ErrorCode::TooManyAttempts),
+            _ => panic!("registration not throttled"),
+        }
+        // Other clients are not affected.
+        assert_eq!(login(&mut room, 2, "geheim"), None);
+        room.disconnect(&1);
+        room.disconnect(&2);
+        assert_eq!(login(&mut room, 1, "geheim"), None);
+    }
+
+    #[test]
+    fn ratings() {
+        let ratings = rate(&[1500.0, 1500.0], &[1, 2]);
+        assert_eq!(ratings, vec![1516.0, 1484.0]);
+        let ratings = rate(&[1500.0, 1500.0, 1500.0], &[1, 1, 3]);
+        assert_eq!(ratings[0], ratings[1]);
+        assert!(ratings[0] > 1500.0 && ratings[2] < 1500.0);
+        // Beating a much better player is worth more than beating an equal.
+        assert!(rate(&[1500.0, 1900.0], &[1, 2])[0] > 1516.0);
+
+        let path = env::temp_dir().join(format!("durak-stats-{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+        let mut stats = Stats::open(path.clone()).unwrap();
+        let placing = |name: &str, place, registered| Placing {
+            name: name.into(),
+            place,
+            durak: place == 3,
+            registered,
+        };
+        let result = GameResult {
+            time: 0,
+            variant: "podkidnoy-5".into(),
+            placings: vec![
+                placing("Kekse", 1, true),
+                placing("Gast", 2, false),
+                placing("Kuchen", 3, true),
+            ],
+        };
+        stats.record(&result).unwrap();
+        stats.record(&result).unwrap();
+        stats.abandon("Kuchen").unwrap();
+        assert!(stats.get("Gast").is_none());
+        let stats = Stats::open(path.clone()).unwrap();
+        assert_eq!(stats.abandoned("Kuchen"), 1);
+        assert_eq!(stats.abandoned("Kekse"), 0);
+        let kuchen = stats.get("Kuchen").unwrap().total();
+        assert_eq!((kuchen.games, kuchen.durak, kuchen.places), (2, 2, 6));
+        assert_eq!(kuchen.win_rate(), 0.0);
+        assert!(stats.rating("Kekse") > DEFAULT_RATING);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn leaderboards() {
+        // Sunday, 2026-10-18 15:30 UTC.
+        let now = 1_792_337_400;
+        assert_eq!(Period::Daily.start(now), 1_792_281_600);
+        assert_eq!(Period::Weekly.start(now), 1_791_763_200);
+        assert_eq!(Period::AllTime.start(now), 0);
+
+        let day = 24 * 60 * 60;
+        let path = env::temp_dir().join(format!("durak-results-{}.txt", process::id()));
+        let _ = fs::remove_file(&path);
+        let mut log = ResultLog::open(path.clone()).unwrap();
+        let result = |time, variant: &str, winner: &str, loser: &str| GameResult {
+            time,
+            variant: variant.into(),
+            placings: vec![
+                Placing {
+                    name: winner.into(),
+                    place: 1,
+                    durak: false,
+                    registered: winner != "Gast",
+                },
+                Placing {
+                    name: loser.into(),
+                    place: 2,
+                    durak: true,
+                    registered: loser != "Gast",
+                },
+            ],
+        };
+        log.append(result(now - 30 * day, "podkidnoy-5", "Kekse", "Kuchen")).unwrap();
+        log.append(result(now - 30 * day, "podkidnoy-5", "Kekse", "Kuchen")).unwrap();
+        log.append(result(now - 2 * day, "podkidnoy-6", "Kuchen", "Kekse")).unwrap();
+        log.append(result(now - 60, "podkidnoy-5", "Gast", "Torte")).unwrap();
+
+        // The log survives a restart.
+        let log = ResultLog::open(path.clone()).unwrap();
+        assert_eq!(log.results().len(), 4);
+        let leaderboard = log.leaderboard(None, Period::AllTime, now);
+        assert_eq!(leaderboard.rating[0].0, "Kekse");
+        assert_eq!(leaderboard.wins, vec![("Kekse".into(), 2), ("Kuchen".into(), 1)]);
+        let leaderboard = log.leaderboard(None, Period::Weekly, now);
+        assert_eq!(leaderboard.rating[0].0, "Kuchen");
+        assert_eq!(leaderboard.rating.len(), 3);
+        let leaderboard = log.leaderboard(Some("podkidnoy-5"), Period::Daily, now);
+        assert_eq!(leaderboard.rating, vec![("Torte".into(), 1484.0)]);
+        assert!(leaderboard.wins.is_empty());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn player_stats() {
+        let mut room = Room::new(DefaultRules::new());
+        let command = Command::parse("player register Kekse geheim").unwrap();
+        assert!(room.handle_command(&1, command).is_none());
+        match room.handle_command(&1, Command::parse("player stats").unwrap()) {
+            Some((_, Answer::PlayerStats(stats))) => {
+                assert_eq!(stats.rating, DEFAULT_RATING);
+                assert_eq!(stats.total().games, 0);
+            }
+            _ => panic!("no statistics"),
+        }
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot").unwrap());
+        let rules = DefaultRules::new();
+        let mut answer = room.handle_command(&1, Command::parse("game start").unwrap());
+        let durak = loop {
+            let state = match answer {
+                Some((_, Answer::GameState(ref state))) => state.clone(),
+                _ => panic!("no game state"),
+            };
+            if state.game_over {
+                break state.durak;
+            }
+            let players: Vec<ClientHash> = state.player_cards.keys().cloned().collect();
+            let action = rules.legal_actions(&1, &state, &players).remove(0);
+            answer = room.handle_command(&1, Command::Game(GameCommand::Action(action)));
+        };
+        match room.handle_command(&2, Command::parse("player stats Kekse").unwrap()) {
+            Some((_, Answer::PlayerStats(stats))) => {
+                let record = stats.records[&(rules.variant(), 2)];
+                assert_eq!(record.games, 1);
+                assert_eq!(record.durak, if durak == Some(1) { 1 } else { 0 });
+                // A draw still ranks whoever ran out of cards first.
+                if durak.is_some() {
+                    assert_ne!(stats.rating, DEFAULT_RATING);
+                }
+            }
+            _ => panic!("no statistics"),
+        }
+        match room.handle_command(&2, Command::parse("leaderboard all-time").unwrap()) {
+            Some((_, Answer::Leaderboard(leaderboard))) => {
+                assert_eq!(leaderboard.rating.len(), 1);
+                assert_eq!(leaderboard.wins.len(), if durak == Some(1) { 0 } else { 1 });
+            }
+            _ => panic!("no leaderboard"),
+        }
+        match room.handle_command(&1, Command::parse("player list").unwrap()) {
+            Some((_, Answer::PlayerList(players))) => {
+                assert!(players[&1].rating.is_some());
+                assert!(players.values().any(|x| x.bot.is_some() && x.rating.is_none()));
+            }
+            _ => panic!("no player list"),
+        }
+        // Guests have no statistics.
+        room.handle_command(&2, Command::parse("player name Gast").unwrap());
+        match room.handle_command(&2, Command::parse("player stats").unwrap()) {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => {
+                assert_eq!(e.code(), ErrorCode::NoStats)
+            }
+            _ => panic!("statistics of a guest"),
+        }
+    }
+
+    #[test]
+    fn player_names() {
+        let mut room = Room::new(DefaultRules::new());
+        let error = |answer: Option<(AnswerTarget, Answer<DefaultRules>)>| match answer {
+            Some((AnswerTarget::Direct, Answer::Error(e))) => e.code(),
+            _ => panic!("no error"),
+        };
+        assert!(room.handle_command(&1, Command::parse("player name Kekse").unwrap()).is_none());
+        for name in &["kekse", "KEKSE"] {
+            let command = Command::parse(format!("player name {}", name)).unwrap();
+            assert_eq!(error(room.handle_command(&2, command)), ErrorCode::NameTaken);
+        }
+        let long = format!("player name {}", "x".repeat(MAX_NAME_LENGTH + 1));
+        for name in &[long.as_str(), "player name Kek/se", "player name Kek\u{7}se"] {
+            let command = Command::parse(*name).unwrap();
+            assert_eq!(error(room.handle_command(&2, command)), ErrorCode::InvalidName);
+        }
+        let command = Command::parse("player name Пирожок_2").unwrap();
+        assert!(room.handle_command(&2, command).is_none());
+        // Changing the case of one's own name is fine.
+        assert!(room.handle_command(&1, Command::parse("player name KEKSE").unwrap()).is_none());
+
+        // The table learns about renames.
+        let tablehash = new_table(&mut room, 1, "table new Kuchen");
+        let join = format!("table join {:016X}", tablehash);
+        room.handle_command(&2, Command::parse(join).unwrap());
+        match room.handle_command(&2, Command::parse("player name Torte").unwrap()) {
+            Some((AnswerTarget::List(mut targets), Answer::Renamed(2, name))) => {
+                targets.sort();
+                assert_eq!(targets, vec![1, 2]);
+                assert_eq!(name, "Torte");
+            }
+            _ => panic!("rename not announced"),
+        }
+    }
+
+    #[test]
+    fn ismcts_bot() {
+        let mut room = Room::new(DefaultRules::new());
+        room.set_config(RoomConfig {
+            bots: BotConfig {
+                budget: Some(Duration::from_millis(5)),
+                ..BotConfig::default()
+            },
+            ..RoomConfig::default()
+        });
+        room.handle_command(&1, Command::parse("player name Kekse").unwrap());
+        new_table(&mut room, 1, "table new Kuchen");
+        room.handle_command(&1, Command::parse("table addbot ismcts").unwrap());
+        let mut answer = room.handle_command(&1, Command::parse("game start").unwrap());
+        let rules = DefaultRules::new();
+        for _ in 0..10000 {
+            let state = match answer.take() {
+                Some((_, Answer::GameState(state))) => state,
+                answer => panic!("unexpected {:?}", answer.map(|x| x.1)),
+            };
+            if state.game_over {
+                return;
+            }
+            let players: Vec<ClientHash> = state.player_cards.keys().cloned().collect();
+            match rules.legal_actions(&1, &state, &players).first() {
+                Some(action) => {
+                    let command = Command::Game(GameCommand::Action(action.clone()));
+                    answer = room.handle_command(&1, command);
+                }
+                // The bot is thinking without blocking the room.
+                None => {
+                    while answer.is_none() {
+                        answer = room.tick().pop();
+                        thread::sleep(Duration::from_millis(1));
+                    }
+                }
+            }
+        }
+        panic!("game did not finish");
+    }
+
+    #[test]
+    fn simulation() {
+        let mut simulation = Simulation::new(vec![BotLevel::Heuristic, BotLevel::Heuristic]);
+        simulation.games = 20;
+        simulation.seed = 42;
+        let report = simulation.run().unwrap();
+        assert_eq!(report.lengths.len() + report.aborted, 20);
+        let durak: usize = report.bots.iter().map(|x| x.durak).sum();
+        assert_eq!(durak + report.draws + report.aborted, 20);
+        assert_eq!(simulation.run().unwrap().lengths, report.lengths);
+
+        let (low, high) = wilson_interval(50, 100);
+        assert!(low < 0.5 && high > 0.5 && high - low < 0.2);
+        let config = SimulateConfig::from_args(
+            vec!["-g", "5", "random", "ismcts"].into_iter().map(String::from),
+        ).unwrap();
+        assert_eq!(config.simulation.bots, vec![BotLevel::Random, BotLevel::Ismcts]);
+        assert!(SimulateConfig::from_args(vec!["random".to_string()]).is_err());
+        // A card has to stay in the talon for the trump.
+        let args = |cards: &str, bots: usize| {
+            let mut args = vec!["--cards".to_string(), cards.to_string()];
+            args.extend((0..bots).map(|_| "random".to_string()));
+            SimulateConfig::from_args(args)
+        };
+        assert!(args("5", 7).is_ok());
+        assert!(args("6", 6).is_err());
+        assert!(args("17", 2).is_ok());
+        assert!(args("18", 2).is_err());
+
+        // Deals without a trump card never finish.
+        let mut simulation = Simulation::new(vec![BotLevel::Random; 6]);
+        simulation.games = 1;
+        simulation.cards_per_player = 6;
+        assert_eq!(simulation.run().unwrap_err().code(), ErrorCode::InvalidGameState);
+    }
+
+    #[test]
+    fn engine() {
+        let config = SimulateConfig::from_args(
+            vec![
+                "-g",
+                "3",
+                "--engine",
+                "first=sh utils/first_move_engine.sh",
+                "first",
+                "heuristic",
+            ].into_iter()
+                .map(String::from),
+        ).unwrap();
+        assert_eq!(
+            config.simulation.bots[0],
+            BotLevel::Engine("first".to_string())
+        );
+        let report = config.simulation.run().unwrap();
+        assert_eq!(report.lengths.len() + report.aborted, 3);
+
+        let missing = Simulation::new(vec![BotLevel::Engine("missing".into()), BotLevel::Random]);
+        assert_eq!(missing.run().unwrap_err().code(), ErrorCode::UnknownBot);
+    }
+
+    /// Polls the server until `client` receives an answer of the kind
+    /// `expected`, skipping everything else.
+    fn receive(
+        server: &mut Server<DefaultRules>,
+        client: &RemoteChannel<DefaultRules>,
+        expected: &str,
+    ) -> Answer<DefaultRules> {
+        for _ in 0..1000 {
+            server.poll().unwrap();
+            while let Some(answer) = client.recv_timeout(Duration::from_millis(1)) {
+                match (expected, answer) {
+                    ("player state", answer @ Answer::PlayerState(..)) |
+                    ("game state", answer @ Answer::GameState(_)) => return answer,
+                    _ => {}
+                }
+            }
+        }
+        panic!("no {}", expected);
+    }
+
+    /// Creates a table for a named client, who sits down at it right away.
+    fn new_table(room: &mut Room<DefaultRules>, client: ClientHash, line: &str) -> TableHash {
+        match room.handle_command(&client, Command::parse(line).unwrap()) {
+            Some((_, Answer::TableSettings(tablehash, _))) => tablehash,
+            _ => panic!("no table created"),
+        }
+    }
 }
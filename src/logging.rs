@@ -0,0 +1,247 @@
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::prelude::*;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use network::*;
+
+#[macro_export]
+macro_rules! durak_log {
+    ($level:ident, $($key:ident = $value:expr),+ ; $($arg:tt)+) => (
+        if $crate::logging::enabled($crate::logging::Level::$level) {
+            $crate::logging::log(
+                $crate::logging::Level::$level,
+                module_path!(),
+                &[$((stringify!($key), $crate::logging::LogValue::log_value(&$value))),+],
+                format_args!($($arg)+),
+            );
+        }
+    );
+    ($level:ident, $($arg:tt)+) => (
+        if $crate::logging::enabled($crate::logging::Level::$level) {
+            $crate::logging::log(
+                $crate::logging::Level::$level,
+                module_path!(),
+                &[],
+                format_args!($($arg)+),
+            );
+        }
+    );
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => (durak_log!(Error, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => (durak_log!(Warn, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => (durak_log!(Info, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => (durak_log!(Debug, $($arg)+));
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => (durak_log!(Trace, $($arg)+));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Destination of formatted log lines.
+pub trait Sink: Send {
+    fn write_line(&mut self, line: &str) -> io::Result<()>;
+}
+
+pub struct StderrSink;
+
+pub struct FileSink {
+    file: File,
+}
+
+/// Converts context fields into their logged representation. Hashes are
+/// logged in the same hexadecimal form as in the protocol.
+pub trait LogValue {
+    fn log_value(&self) -> String;
+}
+
+struct Logger {
+    level: Level,
+    sinks: Vec<Box<dyn Sink>>,
+}
+
+static LOGGER: OnceLock<Mutex<Logger>> = OnceLock::new();
+
+/// Installs the global logger. Until this is called nothing is logged.
+pub fn init(level: Level, sinks: Vec<Box<dyn Sink>>) {
+    let logger = Logger { level, sinks };
+    if let Err(logger) = LOGGER.set(Mutex::new(logger)) {
+        if let Ok(mut current) = LOGGER.get().unwrap().lock() {
+            *current = logger.into_inner().unwrap();
+        }
+    }
+}
+
+pub fn enabled(level: Level) -> bool {
+    match LOGGER.get() {
+        Some(logger) => logger.lock().map(|x| level <= x.level).unwrap_or(false),
+        None => false,
+    }
+}
+
+pub fn log(level: Level, target: &str, fields: &[(&str, String)], args: fmt::Arguments) {
+    let mut line = format!("{} {:5} {}: {}", timestamp(), level, target, args);
+    for &(key, ref value) in fields {
+        line.push_str(&format!(" {}={}", key, value));
+    }
+    if let Some(logger) = LOGGER.get() {
+        if let Ok(mut logger) = logger.lock() {
+            for sink in &mut logger.sinks {
+                let _ = sink.write_line(&line);
+            }
+        }
+    }
+}
+
+/// Formats the current time as UTC in ISO 8601.
+fn timestamp() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0);
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Civil date from days since epoch, see Howard Hinnant's date algorithms.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60
+    )
+}
+
+impl Sink for StderrSink {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(io::stderr(), "{}", line)
+    }
+}
+
+impl FileSink {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<FileSink> {
+        Ok(FileSink {
+            file: OpenOptions::new().create(true).append(true).open(path)?,
+        })
+    }
+}
+
+impl Sink for FileSink {
+    fn write_line(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.file, "{}", line)
+    }
+}
+
+impl LogValue for u64 {
+    fn log_value(&self) -> String {
+        format!("{:016X}", self)
+    }
+}
+
+impl LogValue for usize {
+    fn log_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl LogValue for bool {
+    fn log_value(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl LogValue for str {
+    fn log_value(&self) -> String {
+        format!("{:?}", self)
+    }
+}
+
+impl LogValue for String {
+    fn log_value(&self) -> String {
+        self.as_str().log_value()
+    }
+}
+
+impl<V: LogValue + ?Sized> LogValue for &V {
+    fn log_value(&self) -> String {
+        (**self).log_value()
+    }
+}
+
+impl<V: LogValue> LogValue for Option<V> {
+    fn log_value(&self) -> String {
+        match *self {
+            Some(ref x) => x.log_value(),
+            None => "-".into(),
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match *self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        f.pad(name)
+    }
+}
+
+impl FromStr for Level {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<Level> {
+        match s {
+            "error" => Ok(Level::Error),
+            "warn" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            "trace" => Ok(Level::Trace),
+            x => Err(DurakError::new(
+                ErrorCode::InvalidConfig,
+                format!("Unknown log level {}.", x),
+            )),
+        }
+    }
+}
@@ -1,9 +1,89 @@
+#[macro_use]
 extern crate durak;
 
+use std::env;
+use std::process;
+
+use durak::config::*;
+use durak::logging;
+use durak::logging::*;
 use durak::network::*;
 use durak::rules::*;
+use durak::transport::*;
 
 fn main() {
-    let mut server = Server::new("0.0.0.0:2342", DefaultRules::new()).unwrap();
-    server.listen().unwrap();
+    let mut args = env::args().peekable();
+    let program = args.next().unwrap_or_else(|| "durakserver".into());
+    if args.peek().map(|x| x == "simulate").unwrap_or(false) {
+        args.next();
+        simulate(&program, args);
+        return;
+    }
+    let config = match Config::from_args(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("ERROR {}", e);
+            process::exit(1);
+        }
+    };
+    if config.help {
+        print!("{}", Config::usage(&program));
+        return;
+    }
+    if let Err(e) = run(config) {
+        eprintln!("ERROR {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(config: Config) -> Result<()> {
+    let mut sinks: Vec<Box<dyn Sink>> = Vec::new();
+    if config.log_stderr {
+        sinks.push(Box::new(StderrSink));
+    }
+    if let Some(ref path) = config.log_file {
+        sinks.push(Box::new(FileSink::open(path)?));
+    }
+    logging::init(config.log_level, sinks);
+    let rules = DefaultRules::with_cards_per_player(config.cards_per_player);
+    let mut server = Server::with_rules(rules);
+    server.set_room_config(config.room.clone());
+    server.set_accounts(config.open_accounts()?);
+    server.set_stats(config.open_stats()?);
+    server.set_results(config.open_results()?);
+    for address in config.addresses() {
+        let mut transport = TcpTransport::bind(&address)?;
+        transport.set_timeout(config.client_timeout);
+        server.add_transport(transport);
+        log_info!("Listening on {}.", address);
+    }
+    if let Some(ref path) = config.unix {
+        let mut transport = UnixTransport::bind_with_mode(path, config.unix_mode)?;
+        transport.set_timeout(config.client_timeout);
+        server.add_unix(transport);
+        log_info!("Listening on {}.", path.display());
+    }
+    server.listen()
+}
+
+fn simulate<I: Iterator<Item = String>>(program: &str, args: I) {
+    let config = match SimulateConfig::from_args(args) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("ERROR {}", e);
+            process::exit(1);
+        }
+    };
+    if config.help {
+        print!("{}", SimulateConfig::usage(program));
+        return;
+    }
+    logging::init(config.log_level, vec![Box::new(StderrSink)]);
+    match config.simulation.run() {
+        Ok(report) => print!("{}", report),
+        Err(e) => {
+            eprintln!("ERROR {}", e);
+            process::exit(1);
+        }
+    }
 }
@@ -1,24 +1,28 @@
-use std::u64;
 use std::collections::HashMap;
-use std::error::Error;
+use std::fmt;
 use std::fmt::Debug;
 use std::io;
-use std::io::prelude::*;
-use std::io::{BufReader, BufWriter};
-use std::net::{TcpListener, ToSocketAddrs};
+use std::fs;
+use std::net::ToSocketAddrs;
 use std::num;
-use std::process;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::result;
 use std::sync::mpsc;
 use std::thread;
 use std::time;
 
-use rand::random;
+use account::*;
+use bot::*;
 use game::*;
+use i18n::*;
+use leaderboard::*;
 use rules::*;
+use stats::*;
+use transport::*;
 
 macro_rules! durak_error {
-    ($t:ident, $x:expr) => (DurakError::new(DurakErrorType::$t, $x))
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
 }
 
 pub type ClientHash = u64;
@@ -30,8 +34,11 @@ pub struct DuplexChannel<A, B> {
 }
 
 pub struct Server<T: GameRules + Clone + Send> {
-    listener: TcpListener,
-    channels: HashMap<ClientHash, DuplexChannel<Answer<T>, Command<T>>>,
+    transports: Vec<Box<dyn Transport<T>>>,
+    clients_tx: mpsc::Sender<NewClient<T>>,
+    clients_rx: mpsc::Receiver<NewClient<T>>,
+    channels: HashMap<ClientHash, ClientChannel<T>>,
+    socket_paths: Vec<PathBuf>,
     room: Room<T>,
 }
 
@@ -39,8 +46,9 @@ pub type Result<T> = result::Result<T, DurakError>;
 
 #[derive(Debug, Clone)]
 pub struct DurakError {
-    error_type: DurakErrorType,
+    code: ErrorCode,
     message: String,
+    args: Vec<String>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -49,16 +57,71 @@ pub enum DurakErrorType {
     ChannelSendError,
     ChannelRecvError,
     ParserError,
+    ConfigError,
     GameError,
     Unimplemented,
 }
 
+/// Machine readable reason of an error, sent to clients next to the message.
+/// The protocol names returned by `Display` must never change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    IoError,
+    ChannelSendError,
+    ChannelRecvError,
+    UnknownCommand,
+    MissingArgument,
+    InvalidArgument,
+    InvalidConfig,
+    ServerFull,
+    PlayerNotFound,
+    TableNotFound,
+    TooManyTables,
+    TableFull,
+    AlreadyJoined,
+    NoTableJoined,
+    NotEnoughPlayers,
+    GameRunning,
+    NoGameRunning,
+    InvalidGameState,
+    NotYourTurn,
+    CardNotInHand,
+    CardTooLow,
+    CardNotMatching,
+    CardValueNotOnTable,
+    StackNotFound,
+    StackAlreadyDefended,
+    TooManyStacks,
+    NothingToTake,
+    CannotPass,
+    NotTableOwner,
+    SpectatingDisabled,
+    TableLocked,
+    WrongPassword,
+    InvalidPlayerLimits,
+    NoMatch,
+    SeatAbandoned,
+    NameReserved,
+    InvalidName,
+    NameTaken,
+    NoStats,
+    NotAdmin,
+    UnknownLanguage,
+    UnknownBot,
+    InvalidSetting,
+    Banned,
+    TooManyAttempts,
+    Unimplemented,
+}
+
 #[derive(Clone, Debug)]
 pub enum Command<T: GameRules + Clone + Send> {
     Player(PlayerCommand),
     Table(TableCommand),
     Game(GameCommand),
-    Answer(Answer<T>),
+    /// Best players of a variant, of all variants without one.
+    Leaderboard(Option<String>, Period),
+    Answer(Box<Answer<T>>),
     Quit,
 }
 
@@ -69,7 +132,21 @@ pub enum Answer<T: GameRules + Clone + Send> {
     TableList(HashMap<TableHash, Table<T>>),
     Error(DurakError),
     Chat(ClientHash, String),
-    GameState(GameState),
+    GameState(Box<GameState>),
+    TableSettings(TableHash, Box<Table<T>>),
+    MatchSummary(TableHash, Match),
+    PlayerStats(PlayerStats),
+    Leaderboard(Leaderboard),
+    /// Seconds left for the move of the listed players.
+    MoveTimer(u64, Vec<ClientHash>),
+    /// A player at the table changed the name.
+    Renamed(ClientHash, String),
+    /// Seated players ready for the next game.
+    Ready(Vec<ClientHash>),
+    /// Seconds until the game starts, `None` when the start was called off.
+    Countdown(Option<u64>),
+    /// The owner removed the receiver from the table.
+    Kicked(TableHash),
 }
 
 pub enum AnswerTarget {
@@ -80,244 +157,447 @@ pub enum AnswerTarget {
 #[derive(Debug, Clone)]
 pub enum PlayerCommand {
     Name(String),
+    /// Create an account with name and password and log in to it.
+    Register(String, String),
+    Login(String, String),
+    Lang(Language),
     State,
     List,
+    /// Statistics of the named registered player, one's own without a name.
+    Stats(Option<String>),
 }
 
 #[derive(Debug, Clone)]
 pub enum TableCommand {
-    New(String),
-    Join(TableHash),
+    New(String, TableOptions),
+    /// Sit down at a table, with the password or invite code if it has one.
+    Join(TableHash, Option<String>),
     Chat(String),
     Leave,
     List,
+    AddBot(BotLevel),
+    /// Follow a table without a seat.
+    Watch(TableHash, Option<String>),
+    /// Show the settings of the joined table or change one, owner only.
+    Settings(Option<TableSetting>),
+    /// Remove a player from the table, owner only.
+    Kick(ClientHash),
+    /// Stop or allow new players joining, owner only.
+    Lock(bool),
+    /// Remove the table and everybody at it, owner only.
+    Close,
+    /// Show the running match or set one up, owner only.
+    Match(Option<MatchSetting>),
+}
+
+/// Settings of a new table, given as trailing `password=...`, `private`,
+/// `min=...` or `max=...` words of `table new`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableOptions {
+    pub password: Option<String>,
+    /// Unlisted table joined with an invite code.
+    pub private: bool,
+    pub min_players: Option<usize>,
+    pub max_players: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableSetting {
+    Name(String),
+    Spectators(bool),
+    /// New password, `None` removes it.
+    Password(Option<String>),
+    Private(bool),
+    MinPlayers(usize),
+    MaxPlayers(usize),
+    /// Seconds a player has for a move in this phase, `None` for no limit.
+    Timer(TurnPhase, Option<u64>),
+    Abandon(AbandonPolicy),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatchSetting {
+    /// Play this number of games.
+    Games(usize),
+    /// Play until somebody was durak this often.
+    Score(usize),
+    Off,
 }
 
 #[derive(Debug, Clone)]
 pub enum GameCommand {
     Start,
     State,
+    /// Declare whether the player wants the next game to start.
+    Ready(bool),
     Action(GameAction),
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum GameAction {
     DealCards,
     PutCard(Card, Option<usize>),
+    Take,
+    Pass,
 }
 
 impl<T: GameRules + Debug + Clone + Send + 'static> Server<T> {
     pub fn new<S: ToSocketAddrs>(address: S, rules: T) -> Result<Server<T>> {
-        Ok(Server {
-            listener: TcpListener::bind(address)?,
+        Ok(Server::with_transport(TcpTransport::bind(address)?, rules))
+    }
+
+    /// Creates a server only reachable through the Unix domain socket at
+    /// `path`.
+    pub fn new_unix<P: AsRef<Path>>(path: P, mode: Option<u32>, rules: T) -> Result<Server<T>> {
+        let mut server = Server::with_rules(rules);
+        server.bind_unix(path, mode)?;
+        Ok(server)
+    }
+
+    pub fn with_transport<R: Transport<T> + 'static>(transport: R, rules: T) -> Server<T> {
+        let mut server = Server::with_rules(rules);
+        server.add_transport(transport);
+        server
+    }
+
+    /// Creates a server without any transport attached yet.
+    pub fn with_rules(rules: T) -> Server<T> {
+        let (clients_tx, clients_rx) = mpsc::channel();
+        Server {
+            transports: Vec::new(),
+            clients_tx,
+            clients_rx,
             channels: HashMap::new(),
+            socket_paths: Vec::new(),
             room: Room::new(rules),
-        })
+        }
     }
 
-    pub fn listen(&mut self) -> Result<()> {
-        let listener = self.listener.try_clone()?;
-        let (tx, rx) = mpsc::channel();
-
-        thread::spawn(move || for stream in listener.incoming() {
-            let tx = tx.clone();
-            let stream = stream.unwrap();
-            let id: ClientHash = random();
-            let (remote_channel, local_channel) = DuplexChannel::new();
-            tx.send((id, remote_channel)).unwrap();
-            let tx = local_channel.tx;
-            let local_stream = stream.try_clone().unwrap();
-            thread::spawn(move || {
-                let mut reader = BufReader::new(local_stream);
-                loop {
-                    let mut line = String::new();
-                    match reader.read_line(&mut line) {
-                        Ok(0) => break,
-                        Ok(_) => {
-                            match Command::parse(line) {
-                                Ok(cmd) => {
-                                    tx.send(cmd).unwrap();
-                                }
-                                Err(e) => {
-                                    println!("error {:016X} {:?}", id, e);
-                                    tx.send(Command::Answer(Answer::Error(e))).unwrap();
-                                }
-                            }
-                        }
-                        Err(_) => {}
-                    }
-                }
-            });
-            let rx = local_channel.rx;
-            let local_stream = stream.try_clone().unwrap();
-            thread::spawn(move || {
-                let mut writer = BufWriter::new(local_stream);
-                for answer in rx {
-                    match answer {
-                        Answer::PlayerList(list) => {
-                            for (hash, player) in list {
-                                writer
-                                    .write_fmt(format_args!("{:016X} {}\n", hash, player.name))
-                                    .unwrap();
-                            }
-                        }
-                        Answer::PlayerState(hash, player) => {
-                            writer
-                                .write_fmt(format_args!("hash  {:016X}\n", hash))
-                                .unwrap();
-                            writer
-                                .write_fmt(format_args!("name  {}\n", player.name))
-                                .unwrap();
-                            if let Some(table) = player.table {
-                                writer
-                                    .write_fmt(format_args!("table {:016X}\n", table))
-                                    .unwrap();
-                            }
-                        }
-                        Answer::TableList(list) => {
-                            for (tablehash, table) in list {
-                                writer
-                                    .write_fmt(format_args!(
-                                        "{:016X} {} {} {} {} {}\n",
-                                        tablehash,
-                                        table.players.len(),
-                                        table.min_players,
-                                        table.max_players,
-                                        table.get_state(),
-                                        table.name
-                                    ))
-                                    .unwrap();
-                            }
-                            writer.write_fmt(format_args!("\n")).unwrap();
-                        }
-                        Answer::Error(error) => {
-                            println!("error {:?}", error);
-                            writer
-                                .write_fmt(format_args!("ERROR {}\n", error.to_string()))
-                                .unwrap();
-                        }
-                        Answer::Chat(sender, message) => {
-                            writer
-                                .write_fmt(format_args!("chat {:016X} {}\n", sender, message))
-                                .unwrap();
-                        }
-                        Answer::GameState(gamestate) => {
-                            writer
-                                .write_fmt(format_args!(
-                                    "cards {}\n",
-                                    gamestate.player_cards[&id].iter().fold(
-                                        String::new(),
-                                        |acc, x| {
-                                            if acc.is_empty() {
-                                                format!("{}", x)
-                                            } else {
-                                                format!("{} {}", acc, x)
-                                            }
-                                        },
-                                    )
-                                ))
-                                .unwrap();
-                            writer
-                                .write_fmt(format_args!("trump {}\n", gamestate))
-                                .unwrap();
-                            writer
-                                .write_fmt(format_args!(
-                                    "table {}\n",
-                                    gamestate.table_stacks.iter().fold(String::new(), |acc,
-                                     &(ref x,
-                                       ref y)| {
-                                        if acc.is_empty() {
-                                            match *y {
-                                                Some(ref c) => format!("{}/{}", x, c),
-                                                None => format!("{}/--", x),
-                                            }
-                                        } else {
-                                            match *y {
-                                                Some(ref c) => format!("{} {}/{}", acc, x, c),
-                                                None => format!("{} {}/--", acc, x),
-                                            }
-                                        }
-                                    })
-                                ))
-                                .unwrap();
-                            if let Some(p) = gamestate.target_player {
-                                writer
-                                    .write_fmt(format_args!("target {:016X}\n", p))
-                                    .unwrap();
-                            }
+    pub fn add_transport<R: Transport<T> + 'static>(&mut self, transport: R) {
+        self.transports.push(Box::new(transport));
+    }
 
-                        }
+    /// Additionally listens on the Unix domain socket at `path`. The socket
+    /// file is removed again when the server is dropped.
+    pub fn bind_unix<P: AsRef<Path>>(&mut self, path: P, mode: Option<u32>) -> Result<()> {
+        self.add_unix(UnixTransport::bind_with_mode(path, mode)?);
+        Ok(())
+    }
+
+    pub fn add_unix(&mut self, transport: UnixTransport) {
+        self.socket_paths.push(transport.path().to_path_buf());
+        self.add_transport(transport);
+    }
+
+    pub fn set_room_config(&mut self, config: RoomConfig) {
+        self.room.set_config(config);
+    }
+
+    pub fn set_accounts(&mut self, accounts: Accounts) {
+        self.room.set_accounts(accounts);
+    }
+
+    pub fn set_stats(&mut self, stats: Stats) {
+        self.room.set_stats(stats);
+    }
+
+    pub fn set_results(&mut self, results: ResultLog) {
+        self.room.set_results(results);
+    }
+
+    /// Starts all transports. Transports added afterwards are started on the
+    /// next call.
+    pub fn start(&mut self) -> Result<()> {
+        for transport in self.transports.drain(..) {
+            transport.start(self.clients_tx.clone())?;
+        }
+        Ok(())
+    }
+
+    /// Handles all pending connections and commands once. Returns `false`
+    /// after the admin asked the server to quit.
+    pub fn poll(&mut self) -> Result<bool> {
+        while let Ok((clienthash, channel)) = self.clients_rx.try_recv() {
+            log_info!(client = clienthash; "Client connected.");
+            self.channels.insert(clienthash, channel);
+        }
+        let mut disconnected = Vec::new();
+        let mut answers = Vec::new();
+        for (clienthash, channel) in &self.channels {
+            match channel.rx.try_recv() {
+                Ok(Command::Quit) if self.room.is_admin(clienthash) => {
+                    log_info!(client = *clienthash; "Admin requested shutdown.");
+                    return Ok(false);
+                }
+                Ok(Command::Quit) => {
+                    let error = durak_error!(NotAdmin, "Only the admin may shut down.");
+                    answers.push((*clienthash, (AnswerTarget::Direct, Answer::Error(error))));
+                }
+                Ok(command) => {
+                    if let Some(answer) = self.room.handle_command(clienthash, command) {
+                        answers.push((*clienthash, answer));
                     }
-                    let _ = writer.flush();
                 }
-            });
-        });
-
-        loop {
-            if let Ok((clienthash, channel)) = rx.try_recv() {
-                self.channels.insert(clienthash, channel);
-            }
-            for (clienthash, channel) in &self.channels {
-                match channel.try_recv() {
-                    Ok(Command::Quit) => process::exit(0),
-                    Ok(command) => {
-                        if let Some((target, answer)) =
-                            self.room.handle_command(clienthash, command)
-                        {
-                            match target {
-                                AnswerTarget::Direct => {
-                                    if let Some(ch) = self.channels.get(clienthash) {
-                                        ch.send(answer.clone()).unwrap();
-                                    }
-                                }
-                                AnswerTarget::List(targets) => {
-                                    for target in targets {
-                                        if let Some(ch) = self.channels.get(&target) {
-                                            ch.send(answer.clone()).unwrap();
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                Err(mpsc::TryRecvError::Disconnected) => disconnected.push(*clienthash),
+                Err(mpsc::TryRecvError::Empty) => {}
+            }
+        }
+        let mut notifications = Vec::new();
+        for clienthash in disconnected {
+            log_info!(client = clienthash; "Client disconnected.");
+            self.channels.remove(&clienthash);
+            notifications.extend(self.room.disconnect(&clienthash));
+        }
+        notifications.extend(self.room.tick());
+        for (target, answer) in notifications {
+            if let AnswerTarget::List(targets) = target {
+                for target in targets {
+                    self.send(&target, answer.clone());
+                }
+            }
+        }
+        for (clienthash, (target, answer)) in answers {
+            if let Answer::Error(ref error) = answer {
+                log_debug!(client = clienthash; "Answering with error: {}", error);
+            }
+            match target {
+                AnswerTarget::Direct => self.send(&clienthash, answer),
+                AnswerTarget::List(targets) => {
+                    for target in targets {
+                        self.send(&target, answer.clone());
                     }
-                    Err(_) => {}
                 }
             }
+        }
+        Ok(true)
+    }
+
+    pub fn listen(&mut self) -> Result<()> {
+        self.start()?;
+        while self.poll()? {
             thread::sleep(time::Duration::from_millis(1));
         }
+        Ok(())
+    }
+
+    fn send(&self, client: &ClientHash, answer: Answer<T>) {
+        if let Some(ch) = self.channels.get(client) {
+            let _ = ch.send(self.room.answer_for(client, answer));
+        }
+    }
+}
+
+impl<T: GameRules + Clone + Send> Drop for Server<T> {
+    fn drop(&mut self) {
+        for path in &self.socket_paths {
+            let _ = fs::remove_file(path);
+        }
     }
 }
 
 impl DurakError {
-    pub fn new<S: Into<String>>(t: DurakErrorType, m: S) -> DurakError {
+    pub fn new<S: Into<String>>(c: ErrorCode, m: S) -> DurakError {
         DurakError {
-            error_type: t,
+            code: c,
             message: m.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Adds a value to be filled into the translated message.
+    pub fn arg<S: Into<String>>(mut self, arg: S) -> DurakError {
+        self.args.push(arg.into());
+        self
+    }
+
+    /// Translates the message. English keeps the more specific message the
+    /// error was created with.
+    pub fn localized(self, language: Language) -> DurakError {
+        if language == Language::English {
+            self
+        } else {
+            DurakError {
+                message: translate(language, self.code, &self.args),
+                ..self
+            }
+        }
+    }
+
+    pub fn code(&self) -> ErrorCode {
+        self.code
+    }
+
+    pub fn error_type(&self) -> DurakErrorType {
+        self.code.error_type()
+    }
+
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+}
+
+impl ErrorCode {
+    pub const ALL: &'static [ErrorCode] = &[
+        ErrorCode::IoError,
+        ErrorCode::ChannelSendError,
+        ErrorCode::ChannelRecvError,
+        ErrorCode::UnknownCommand,
+        ErrorCode::MissingArgument,
+        ErrorCode::InvalidArgument,
+        ErrorCode::InvalidConfig,
+        ErrorCode::ServerFull,
+        ErrorCode::PlayerNotFound,
+        ErrorCode::TableNotFound,
+        ErrorCode::TooManyTables,
+        ErrorCode::TableFull,
+        ErrorCode::AlreadyJoined,
+        ErrorCode::NoTableJoined,
+        ErrorCode::NotEnoughPlayers,
+        ErrorCode::GameRunning,
+        ErrorCode::NoGameRunning,
+        ErrorCode::InvalidGameState,
+        ErrorCode::NotYourTurn,
+        ErrorCode::CardNotInHand,
+        ErrorCode::CardTooLow,
+        ErrorCode::CardNotMatching,
+        ErrorCode::CardValueNotOnTable,
+        ErrorCode::StackNotFound,
+        ErrorCode::StackAlreadyDefended,
+        ErrorCode::TooManyStacks,
+        ErrorCode::NothingToTake,
+        ErrorCode::CannotPass,
+        ErrorCode::NotTableOwner,
+        ErrorCode::SpectatingDisabled,
+        ErrorCode::TableLocked,
+        ErrorCode::WrongPassword,
+        ErrorCode::InvalidPlayerLimits,
+        ErrorCode::NoMatch,
+        ErrorCode::SeatAbandoned,
+        ErrorCode::NameReserved,
+        ErrorCode::InvalidName,
+        ErrorCode::NameTaken,
+        ErrorCode::NoStats,
+        ErrorCode::NotAdmin,
+        ErrorCode::UnknownLanguage,
+        ErrorCode::UnknownBot,
+        ErrorCode::InvalidSetting,
+        ErrorCode::Banned,
+        ErrorCode::TooManyAttempts,
+        ErrorCode::Unimplemented,
+    ];
+
+    pub fn error_type(&self) -> DurakErrorType {
+        match *self {
+            ErrorCode::IoError => DurakErrorType::IOError,
+            ErrorCode::ChannelSendError => DurakErrorType::ChannelSendError,
+            ErrorCode::ChannelRecvError => DurakErrorType::ChannelRecvError,
+            ErrorCode::UnknownCommand |
+            ErrorCode::MissingArgument |
+            ErrorCode::InvalidArgument => DurakErrorType::ParserError,
+            ErrorCode::InvalidConfig => DurakErrorType::ConfigError,
+            ErrorCode::Unimplemented => DurakErrorType::Unimplemented,
+            _ => DurakErrorType::GameError,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match *self {
+            ErrorCode::IoError => "IO_ERROR",
+            ErrorCode::ChannelSendError => "CHANNEL_SEND_ERROR",
+            ErrorCode::ChannelRecvError => "CHANNEL_RECV_ERROR",
+            ErrorCode::UnknownCommand => "UNKNOWN_COMMAND",
+            ErrorCode::MissingArgument => "MISSING_ARGUMENT",
+            ErrorCode::InvalidArgument => "INVALID_ARGUMENT",
+            ErrorCode::InvalidConfig => "INVALID_CONFIG",
+            ErrorCode::ServerFull => "SERVER_FULL",
+            ErrorCode::PlayerNotFound => "PLAYER_NOT_FOUND",
+            ErrorCode::TableNotFound => "TABLE_NOT_FOUND",
+            ErrorCode::TooManyTables => "TOO_MANY_TABLES",
+            ErrorCode::TableFull => "TABLE_FULL",
+            ErrorCode::AlreadyJoined => "ALREADY_JOINED",
+            ErrorCode::NoTableJoined => "NO_TABLE_JOINED",
+            ErrorCode::NotEnoughPlayers => "NOT_ENOUGH_PLAYERS",
+            ErrorCode::GameRunning => "GAME_RUNNING",
+            ErrorCode::NoGameRunning => "NO_GAME_RUNNING",
+            ErrorCode::InvalidGameState => "INVALID_GAME_STATE",
+            ErrorCode::NotYourTurn => "NOT_YOUR_TURN",
+            ErrorCode::CardNotInHand => "CARD_NOT_IN_HAND",
+            ErrorCode::CardTooLow => "CARD_TOO_LOW",
+            ErrorCode::CardNotMatching => "CARD_NOT_MATCHING",
+            ErrorCode::CardValueNotOnTable => "CARD_VALUE_NOT_ON_TABLE",
+            ErrorCode::StackNotFound => "STACK_NOT_FOUND",
+            ErrorCode::StackAlreadyDefended => "STACK_ALREADY_DEFENDED",
+            ErrorCode::TooManyStacks => "TOO_MANY_STACKS",
+            ErrorCode::NothingToTake => "NOTHING_TO_TAKE",
+            ErrorCode::CannotPass => "CANNOT_PASS",
+            ErrorCode::NotTableOwner => "NOT_TABLE_OWNER",
+            ErrorCode::SpectatingDisabled => "SPECTATING_DISABLED",
+            ErrorCode::TableLocked => "TABLE_LOCKED",
+            ErrorCode::WrongPassword => "WRONG_PASSWORD",
+            ErrorCode::InvalidPlayerLimits => "INVALID_PLAYER_LIMITS",
+            ErrorCode::NoMatch => "NO_MATCH",
+            ErrorCode::SeatAbandoned => "SEAT_ABANDONED",
+            ErrorCode::NameReserved => "NAME_RESERVED",
+            ErrorCode::InvalidName => "INVALID_NAME",
+            ErrorCode::NameTaken => "NAME_TAKEN",
+            ErrorCode::NoStats => "NO_STATS",
+            ErrorCode::NotAdmin => "NOT_ADMIN",
+            ErrorCode::UnknownLanguage => "UNKNOWN_LANGUAGE",
+            ErrorCode::UnknownBot => "UNKNOWN_BOT",
+            ErrorCode::InvalidSetting => "INVALID_SETTING",
+            ErrorCode::Banned => "BANNED",
+            ErrorCode::TooManyAttempts => "TOO_MANY_ATTEMPTS",
+            ErrorCode::Unimplemented => "UNIMPLEMENTED",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl FromStr for ErrorCode {
+    type Err = DurakError;
+    fn from_str(s: &str) -> Result<ErrorCode> {
+        match ErrorCode::ALL.iter().find(|x| x.name() == s) {
+            Some(code) => Ok(*code),
+            None => Err(durak_error!(
+                InvalidArgument,
+                format!("Unknown error code {}.", s)
+            )),
         }
     }
 }
 
 impl From<io::Error> for DurakError {
     fn from(e: io::Error) -> DurakError {
-        DurakError::new(DurakErrorType::IOError, e.description())
+        durak_error!(IoError, e.to_string())
     }
 }
 
 impl From<mpsc::TryRecvError> for DurakError {
     fn from(e: mpsc::TryRecvError) -> DurakError {
-        DurakError::new(DurakErrorType::ChannelRecvError, e.description())
+        durak_error!(ChannelRecvError, e.to_string())
     }
 }
 
 impl From<num::ParseIntError> for DurakError {
     fn from(e: num::ParseIntError) -> DurakError {
-        DurakError::new(DurakErrorType::ParserError, e.description())
+        durak_error!(InvalidArgument, e.to_string())
+    }
+}
+
+impl From<num::ParseFloatError> for DurakError {
+    fn from(e: num::ParseFloatError) -> DurakError {
+        durak_error!(InvalidArgument, e.to_string())
     }
 }
 
 impl<T: Send> From<mpsc::SendError<T>> for DurakError {
     fn from(e: mpsc::SendError<T>) -> DurakError {
-        DurakError::new(DurakErrorType::ChannelSendError, e.description())
+        durak_error!(ChannelSendError, e.to_string())
     }
 }
 
@@ -329,26 +609,38 @@ impl<T: GameRules + Clone + Send> Command<T> {
 
         match parts.next() {
             Some("quit") => Ok(Command::Quit),
+            Some("leaderboard") => {
+                let mut variant = None;
+                let mut period = Period::default();
+                for word in parts.next().unwrap_or("").split_whitespace() {
+                    match word.parse() {
+                        Ok(x) => period = x,
+                        Err(_) if variant.is_none() => variant = Some(word.to_string()),
+                        Err(e) => return Err(e),
+                    }
+                }
+                Ok(Command::Leaderboard(variant, period))
+            }
             Some("player") => {
                 match parts.next() {
                     Some(tail) => Ok(Command::Player(PlayerCommand::parse(tail)?)),
-                    None => Err(durak_error!(ParserError, "No player command specified.")),
+                    None => Err(durak_error!(MissingArgument, "No player command specified.")),
                 }
             }
             Some("table") => {
                 match parts.next() {
                     Some(tail) => Ok(Command::Table(TableCommand::parse(tail)?)),
-                    None => Err(durak_error!(ParserError, "No table command specified.")),
+                    None => Err(durak_error!(MissingArgument, "No table command specified.")),
                 }
             }
             Some("game") => {
                 match parts.next() {
                     Some(tail) => Ok(Command::Game(GameCommand::parse(tail)?)),
-                    None => Err(durak_error!(ParserError, "No game command specified.")),
+                    None => Err(durak_error!(MissingArgument, "No game command specified.")),
                 }
             }
-            Some(x) => Err(durak_error!(ParserError, format!("Unknown command {}.", x))),
-            None => Err(durak_error!(ParserError, "No command specified.")),
+            Some(x) => Err(durak_error!(UnknownCommand, format!("Unknown command {}.", x)).arg(x)),
+            None => Err(durak_error!(MissingArgument, "No command specified.")),
         }
     }
 }
@@ -362,16 +654,34 @@ impl PlayerCommand {
             Some("name") => {
                 match parts.next() {
                     Some(name) => Ok(PlayerCommand::Name(name.trim().into())),
-                    None => Err(durak_error!(ParserError, "No name specified.")),
+                    None => Err(durak_error!(MissingArgument, "No name specified.")),
+                }
+            }
+            Some("lang") => {
+                match parts.next() {
+                    Some(lang) => Ok(PlayerCommand::Lang(lang.trim().parse()?)),
+                    None => Err(durak_error!(MissingArgument, "No language specified.")),
                 }
             }
+            Some("register") => {
+                let (name, password) = parse_credentials(parts.next())?;
+                Ok(PlayerCommand::Register(name, password))
+            }
+            Some("login") => {
+                let (name, password) = parse_credentials(parts.next())?;
+                Ok(PlayerCommand::Login(name, password))
+            }
             Some("list") => Ok(PlayerCommand::List),
             Some("state") => Ok(PlayerCommand::State),
+            Some("stats") => {
+                let name = parts.next().map(|x| x.trim()).filter(|x| !x.is_empty());
+                Ok(PlayerCommand::Stats(name.map(String::from)))
+            }
             Some(x) => Err(durak_error!(
-                ParserError,
+                UnknownCommand,
                 format!("Unknown player command {}.", x)
-            )),
-            None => Err(durak_error!(ParserError, "No player command specified.")),
+            ).arg(format!("player {}", x))),
+            None => Err(durak_error!(MissingArgument, "No player command specified.")),
         }
     }
 }
@@ -383,35 +693,182 @@ impl TableCommand {
 
         match parts.next() {
             Some("new") => {
-                match parts.next() {
-                    Some(name) => Ok(TableCommand::New(name.trim().into())),
-                    None => Err(durak_error!(ParserError, "No table name specified.")),
+                let mut words: Vec<&str> = parts.next().unwrap_or("").split_whitespace().collect();
+                let mut options = TableOptions::default();
+                while let Some(&word) = words.last() {
+                    if let Some(password) = word.strip_prefix("password=") {
+                        if password.is_empty() {
+                            return Err(durak_error!(MissingArgument, "No password specified."));
+                        }
+                        options.password = Some(password.into());
+                    } else if word == "private" {
+                        options.private = true;
+                    } else if let Some(min) = word.strip_prefix("min=") {
+                        options.min_players = Some(parse_count(min)?);
+                    } else if let Some(max) = word.strip_prefix("max=") {
+                        options.max_players = Some(parse_count(max)?);
+                    } else {
+                        break;
+                    }
+                    words.pop();
                 }
+                if options.private && options.password.is_some() {
+                    return Err(durak_error!(
+                        InvalidArgument,
+                        "Private tables are joined with an invite code instead of a password."
+                    ));
+                }
+                if words.is_empty() {
+                    return Err(durak_error!(MissingArgument, "No table name specified."));
+                }
+                Ok(TableCommand::New(words.join(" "), options))
             }
             Some("join") => {
+                let (tablehash, secret) = parse_table_secret(parts.next())?;
+                Ok(TableCommand::Join(tablehash, secret))
+            }
+            Some("watch") => {
+                let (tablehash, secret) = parse_table_secret(parts.next())?;
+                Ok(TableCommand::Watch(tablehash, secret))
+            }
+            Some("settings") => {
+                match parts.next() {
+                    Some(setting) => Ok(TableCommand::Settings(Some(TableSetting::parse(setting)?))),
+                    None => Ok(TableCommand::Settings(None)),
+                }
+            }
+            Some("kick") => {
                 match parts.next() {
                     Some(id) => {
-                        match TableHash::from_str_radix(id, 16) {
-                            Ok(tablehash) => Ok(TableCommand::Join(tablehash)),
-                            Err(_) => Err(durak_error!(ParserError, "Could not parse table hash.")),
+                        match ClientHash::from_str_radix(id.trim(), 16) {
+                            Ok(clienthash) => Ok(TableCommand::Kick(clienthash)),
+                            Err(_) => Err(durak_error!(InvalidArgument, "Could not parse player hash.")),
                         }
                     }
-                    None => Err(durak_error!(ParserError, "No table hash specified.")),
+                    None => Err(durak_error!(MissingArgument, "No player hash specified.")),
+                }
+            }
+            Some("lock") => Ok(TableCommand::Lock(true)),
+            Some("unlock") => Ok(TableCommand::Lock(false)),
+            Some("close") => Ok(TableCommand::Close),
+            Some("match") => {
+                match parts.next() {
+                    Some(setting) => Ok(TableCommand::Match(Some(MatchSetting::parse(setting)?))),
+                    None => Ok(TableCommand::Match(None)),
                 }
             }
             Some("list") => Ok(TableCommand::List),
             Some("leave") => Ok(TableCommand::Leave),
+            Some("addbot") => {
+                match parts.next() {
+                    Some(level) => Ok(TableCommand::AddBot(level.trim().parse()?)),
+                    None => Ok(TableCommand::AddBot(BotLevel::default())),
+                }
+            }
             Some("chat") => {
                 match parts.next() {
                     Some(message) => Ok(TableCommand::Chat(message.into())),
-                    None => Err(durak_error!(ParserError, "No message specified.")),
+                    None => Err(durak_error!(MissingArgument, "No message specified.")),
                 }
             }
             Some(x) => Err(durak_error!(
-                ParserError,
+                UnknownCommand,
                 format!("Unknown table command {}.", x)
-            )),
-            None => Err(durak_error!(ParserError, "No table command specified.")),
+            ).arg(format!("table {}", x))),
+            None => Err(durak_error!(MissingArgument, "No table command specified.")),
+        }
+    }
+}
+
+/// Parses `<name> <password>` of `player register` and `player login`.
+fn parse_credentials(arguments: Option<&str>) -> Result<(String, String)> {
+    let mut parts = arguments.unwrap_or("").trim().splitn(2, ' ');
+    match (parts.next(), parts.next()) {
+        (Some(name), Some(password)) if !name.is_empty() && !password.is_empty() => {
+            Ok((name.into(), password.into()))
+        }
+        _ => Err(durak_error!(MissingArgument, "Name and password required.")),
+    }
+}
+
+fn parse_count(s: &str) -> Result<usize> {
+    s.parse().map_err(|_| durak_error!(InvalidArgument, "Could not parse number of players."))
+}
+
+/// Parses `<hash> [password]` of `table join` and `table watch`.
+fn parse_table_secret(arguments: Option<&str>) -> Result<(TableHash, Option<String>)> {
+    let mut parts = arguments.unwrap_or("").split_whitespace();
+    let tablehash = match parts.next() {
+        Some(id) => {
+            match TableHash::from_str_radix(id, 16) {
+                Ok(tablehash) => tablehash,
+                Err(_) => return Err(durak_error!(InvalidArgument, "Could not parse table hash.")),
+            }
+        }
+        None => return Err(durak_error!(MissingArgument, "No table hash specified.")),
+    };
+    Ok((tablehash, parts.next().map(|x| x.into())))
+}
+
+impl TableSetting {
+    pub fn parse<S: Into<String>>(line: S) -> Result<TableSetting> {
+        let line: String = line.into().trim().into();
+        let mut parts = line.splitn(2, ' ');
+
+        match (parts.next(), parts.next().map(|x| x.trim())) {
+            (Some("name"), Some(name)) if !name.is_empty() => Ok(TableSetting::Name(name.into())),
+            (Some("spectators"), Some("on")) => Ok(TableSetting::Spectators(true)),
+            (Some("spectators"), Some("off")) => Ok(TableSetting::Spectators(false)),
+            (Some("password"), Some("off")) => Ok(TableSetting::Password(None)),
+            (Some("password"), Some(password)) if !password.contains(' ') => {
+                Ok(TableSetting::Password(Some(password.into())))
+            }
+            (Some("private"), Some("on")) => Ok(TableSetting::Private(true)),
+            (Some("private"), Some("off")) => Ok(TableSetting::Private(false)),
+            (Some("min"), Some(min)) => Ok(TableSetting::MinPlayers(parse_count(min)?)),
+            (Some("max"), Some(max)) => Ok(TableSetting::MaxPlayers(parse_count(max)?)),
+            (Some("abandon"), Some(policy)) => Ok(TableSetting::Abandon(policy.parse()?)),
+            (Some("timer"), Some(timer)) => {
+                let mut parts = timer.split_whitespace();
+                let phase = parts.next().unwrap_or("").parse()?;
+                match parts.next() {
+                    Some("off") => Ok(TableSetting::Timer(phase, None)),
+                    Some(seconds) => {
+                        match seconds.parse() {
+                            Ok(seconds) => Ok(TableSetting::Timer(phase, Some(seconds))),
+                            Err(_) => {
+                                Err(durak_error!(InvalidArgument, "Could not parse seconds."))
+                            }
+                        }
+                    }
+                    None => Err(durak_error!(MissingArgument, "No seconds specified.")),
+                }
+            }
+            (Some(x), Some(_)) => Err(durak_error!(
+                InvalidSetting,
+                format!("Invalid value for table setting {}.", x)
+            ).arg(x)),
+            _ => Err(durak_error!(MissingArgument, "No table setting value specified.")),
+        }
+    }
+}
+
+impl MatchSetting {
+    pub fn parse(line: &str) -> Result<MatchSetting> {
+        let mut parts = line.split_whitespace();
+        let count = |x: Option<&str>| match x {
+            Some(count) => {
+                count.parse().map_err(|_| {
+                    durak_error!(InvalidArgument, "Could not parse number of games.")
+                })
+            }
+            None => Err(durak_error!(MissingArgument, "No number of games specified.")),
+        };
+        match parts.next() {
+            Some("games") => Ok(MatchSetting::Games(count(parts.next())?)),
+            Some("score") => Ok(MatchSetting::Score(count(parts.next())?)),
+            Some("off") => Ok(MatchSetting::Off),
+            _ => Err(durak_error!(InvalidArgument, "Expected games, score or off.")),
         }
     }
 }
@@ -424,6 +881,10 @@ impl GameCommand {
         match parts.next() {
             Some("start") => Ok(GameCommand::Start),
             Some("state") => Ok(GameCommand::State),
+            Some("ready") => Ok(GameCommand::Ready(true)),
+            Some("unready") => Ok(GameCommand::Ready(false)),
+            Some("take") => Ok(GameCommand::Action(GameAction::Take)),
+            Some("pass") => Ok(GameCommand::Action(GameAction::Pass)),
             Some("put") => {
                 match parts.next() {
                     Some(tail) => {
@@ -440,17 +901,143 @@ impl GameCommand {
                                     )),
                                 }
                             }
-                            None => Err(durak_error!(ParserError, "No card specified.")),
+                            None => Err(durak_error!(MissingArgument, "No card specified.")),
                         }
                     }
-                    None => Err(durak_error!(ParserError, "No card specified.")),
+                    None => Err(durak_error!(MissingArgument, "No card specified.")),
                 }
             }
             Some(x) => Err(durak_error!(
-                ParserError,
+                UnknownCommand,
                 format!("Unknown game command {}.", x)
-            )),
-            None => Err(durak_error!(ParserError, "No table command specified.")),
+            ).arg(format!("game {}", x))),
+            None => Err(durak_error!(MissingArgument, "No table command specified.")),
+        }
+    }
+}
+
+impl<T: GameRules + Clone + Send> fmt::Display for Command<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Command::Player(ref command) => write!(f, "player {}", command),
+            Command::Table(ref command) => write!(f, "table {}", command),
+            Command::Game(ref command) => write!(f, "game {}", command),
+            Command::Leaderboard(Some(ref variant), period) => {
+                write!(f, "leaderboard {} {}", variant, period)
+            }
+            Command::Leaderboard(None, period) => write!(f, "leaderboard {}", period),
+            Command::Answer(_) => Ok(()),
+            Command::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+impl fmt::Display for PlayerCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlayerCommand::Name(ref name) => write!(f, "name {}", name),
+            PlayerCommand::Register(ref name, ref password) => {
+                write!(f, "register {} {}", name, password)
+            }
+            PlayerCommand::Login(ref name, ref password) => {
+                write!(f, "login {} {}", name, password)
+            }
+            PlayerCommand::Lang(lang) => write!(f, "lang {}", lang),
+            PlayerCommand::State => write!(f, "state"),
+            PlayerCommand::List => write!(f, "list"),
+            PlayerCommand::Stats(Some(ref name)) => write!(f, "stats {}", name),
+            PlayerCommand::Stats(None) => write!(f, "stats"),
+        }
+    }
+}
+
+impl fmt::Display for TableCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TableCommand::New(ref name, ref options) => {
+                write!(f, "new {}", name)?;
+                if let Some(ref password) = options.password {
+                    write!(f, " password={}", password)?;
+                }
+                if options.private {
+                    write!(f, " private")?;
+                }
+                if let Some(min) = options.min_players {
+                    write!(f, " min={}", min)?;
+                }
+                if let Some(max) = options.max_players {
+                    write!(f, " max={}", max)?;
+                }
+                Ok(())
+            }
+            TableCommand::Join(tablehash, Some(ref secret)) => {
+                write!(f, "join {:016X} {}", tablehash, secret)
+            }
+            TableCommand::Join(tablehash, None) => write!(f, "join {:016X}", tablehash),
+            TableCommand::Chat(ref message) => write!(f, "chat {}", message),
+            TableCommand::Leave => write!(f, "leave"),
+            TableCommand::List => write!(f, "list"),
+            TableCommand::AddBot(ref level) => write!(f, "addbot {}", level),
+            TableCommand::Watch(tablehash, Some(ref secret)) => {
+                write!(f, "watch {:016X} {}", tablehash, secret)
+            }
+            TableCommand::Watch(tablehash, None) => write!(f, "watch {:016X}", tablehash),
+            TableCommand::Settings(Some(ref setting)) => write!(f, "settings {}", setting),
+            TableCommand::Settings(None) => write!(f, "settings"),
+            TableCommand::Kick(clienthash) => write!(f, "kick {:016X}", clienthash),
+            TableCommand::Lock(true) => write!(f, "lock"),
+            TableCommand::Lock(false) => write!(f, "unlock"),
+            TableCommand::Close => write!(f, "close"),
+            TableCommand::Match(Some(ref setting)) => write!(f, "match {}", setting),
+            TableCommand::Match(None) => write!(f, "match"),
+        }
+    }
+}
+
+impl fmt::Display for MatchSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MatchSetting::Games(games) => write!(f, "games {}", games),
+            MatchSetting::Score(score) => write!(f, "score {}", score),
+            MatchSetting::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl fmt::Display for TableSetting {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TableSetting::Name(ref name) => write!(f, "name {}", name),
+            TableSetting::Spectators(true) => write!(f, "spectators on"),
+            TableSetting::Spectators(false) => write!(f, "spectators off"),
+            TableSetting::Password(Some(ref password)) => write!(f, "password {}", password),
+            TableSetting::Password(None) => write!(f, "password off"),
+            TableSetting::Private(true) => write!(f, "private on"),
+            TableSetting::Private(false) => write!(f, "private off"),
+            TableSetting::MinPlayers(min) => write!(f, "min {}", min),
+            TableSetting::MaxPlayers(max) => write!(f, "max {}", max),
+            TableSetting::Timer(phase, Some(seconds)) => write!(f, "timer {} {}", phase, seconds),
+            TableSetting::Timer(phase, None) => write!(f, "timer {} off", phase),
+            TableSetting::Abandon(policy) => write!(f, "abandon {}", policy),
+        }
+    }
+}
+
+impl fmt::Display for GameCommand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GameCommand::Start => write!(f, "start"),
+            GameCommand::State => write!(f, "state"),
+            GameCommand::Ready(true) => write!(f, "ready"),
+            GameCommand::Ready(false) => write!(f, "unready"),
+            GameCommand::Action(GameAction::PutCard(ref card, Some(stack))) => {
+                write!(f, "put {} {}", card, stack)
+            }
+            GameCommand::Action(GameAction::PutCard(ref card, None)) => write!(f, "put {}", card),
+            GameCommand::Action(GameAction::Take) => write!(f, "take"),
+            GameCommand::Action(GameAction::Pass) => write!(f, "pass"),
+            // Dealing is triggered by "game start" and has no command of its own.
+            GameCommand::Action(GameAction::DealCards) => write!(f, "start"),
         }
     }
 }
@@ -472,6 +1059,14 @@ impl<A: Send, B: Clone> DuplexChannel<A, B> {
     pub fn send(&self, t: A) -> Result<()> {
         self.tx.send(t).map_err(|e| e.into())
     }
+
+    pub fn recv_timeout(&self, timeout: time::Duration) -> Option<B> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    pub fn split(self) -> (mpsc::Sender<A>, mpsc::Receiver<B>) {
+        (self.tx, self.rx)
+    }
 }
 
 impl<A, B> IntoIterator for DuplexChannel<A, B> {
@@ -483,12 +1078,12 @@ impl<A, B> IntoIterator for DuplexChannel<A, B> {
     }
 }
 
-impl ToString for DurakError {
-    fn to_string(&self) -> String {
-        if self.error_type == DurakErrorType::Unimplemented {
-            "Unimplemented feature.".into()
+impl fmt::Display for DurakError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.code == ErrorCode::Unimplemented {
+            write!(f, "Unimplemented feature.")
         } else {
-            self.message.clone()
+            write!(f, "{}", self.message)
         }
     }
 }
@@ -1,29 +1,104 @@
 use network::*;
 use game::*;
 use std::collections::HashSet;
-use rand::{thread_rng, Rng};
+use rand::{thread_rng, Rng, SeedableRng, StdRng};
 
 // TODO import
 macro_rules! durak_error {
-    ($t:ident, $x:expr) => (DurakError::new(DurakErrorType::$t, $x))
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
 }
 
+/// Most stacks a single round may have.
+pub const MAX_STACKS: usize = 6;
+
+/// Fewest players a game can be played with.
+pub const MIN_PLAYERS: usize = 2;
+
 pub trait GameRules {
-    fn apply(&self, &ClientHash, &mut GameState, &[ClientHash], GameAction) -> Result<GameState>;
+    fn apply(
+        &self,
+        origin: &ClientHash,
+        state: &mut GameState,
+        players: &[ClientHash],
+        action: GameAction,
+    ) -> Result<GameState>;
+
+    /// Most players the deck can be dealt to.
+    fn max_players(&self) -> usize;
+
+    /// Name of the rule variant, statistics are kept apart per variant.
+    fn variant(&self) -> String;
+
+    /// Actions `origin` may take in `state`, found by trying every candidate
+    /// on a copy of the state.
+    fn legal_actions(
+        &self,
+        origin: &ClientHash,
+        state: &GameState,
+        players: &[ClientHash],
+    ) -> Vec<GameAction> {
+        let mut candidates = Vec::new();
+        if let Some(cards) = state.player_cards.get(origin) {
+            let mut cards: Vec<&Card> = cards.iter().collect();
+            cards.sort();
+            for card in cards {
+                candidates.push(GameAction::PutCard(card.clone(), None));
+                for (i, (_, defense)) in state.table_stacks.iter().enumerate() {
+                    if defense.is_none() {
+                        candidates.push(GameAction::PutCard(card.clone(), Some(i)));
+                    }
+                }
+            }
+        }
+        candidates.push(GameAction::Take);
+        candidates.push(GameAction::Pass);
+        candidates
+            .into_iter()
+            .filter(|action| {
+                self.apply(origin, &mut state.clone(), players, action.clone()).is_ok()
+            })
+            .collect()
+    }
 }
 
 #[derive(Clone, Debug, Default)]
 pub struct DefaultRules {
     cards_per_player: usize,
+    seed: Option<usize>,
 }
 
 impl DefaultRules {
     pub fn new() -> DefaultRules {
-        DefaultRules { cards_per_player: 5 }
+        DefaultRules::with_cards_per_player(5)
+    }
+
+    pub fn with_cards_per_player(cards_per_player: usize) -> DefaultRules {
+        DefaultRules {
+            cards_per_player,
+            seed: None,
+        }
+    }
+
+    /// Shuffles the deck with a fixed seed, so that every game is dealt the
+    /// same cards.
+    pub fn seeded(self, seed: usize) -> DefaultRules {
+        DefaultRules {
+            seed: Some(seed),
+            ..self
+        }
     }
 }
 
 impl GameRules for DefaultRules {
+    fn max_players(&self) -> usize {
+        // One card has to stay in the talon to decide the trump.
+        (Card::deck().len() - 1) / self.cards_per_player.max(1)
+    }
+
+    fn variant(&self) -> String {
+        format!("podkidnoy-{}", self.cards_per_player)
+    }
+
     fn apply(
         &self,
         origin: &ClientHash,
@@ -31,30 +106,20 @@ impl GameRules for DefaultRules {
         players: &[ClientHash],
         action: GameAction,
     ) -> Result<GameState> {
-        let mut rng = thread_rng();
+        if state.game_over && !matches!(action, GameAction::DealCards) {
+            return Err(durak_error!(InvalidGameState, "Game is over."));
+        }
         match action {
             GameAction::DealCards => {
-                let mut cards = Vec::new();
-                for suite in &[Suite::Hearts, Suite::Diamonds, Suite::Clubs, Suite::Spades] {
-                    for value in &[
-                        CardValue::Number6,
-                        CardValue::Number7,
-                        CardValue::Number8,
-                        CardValue::Number9,
-                        CardValue::Number10,
-                        CardValue::Jack,
-                        CardValue::Queen,
-                        CardValue::King,
-                        CardValue::Ace,
-                    ]
-                    {
-                        cards.push(Card {
-                            suite: suite.clone(),
-                            value: value.clone(),
-                        });
+                let mut cards = Card::deck();
+                match self.seed {
+                    Some(seed) => {
+                        let mut rng: StdRng = SeedableRng::from_seed(&[seed][..]);
+                        rng.shuffle(cards.as_mut_slice());
                     }
+                    None => thread_rng().shuffle(cards.as_mut_slice()),
                 }
-                rng.shuffle(cards.as_mut_slice());
+                *state = GameState::new();
                 for player in players {
                     state.player_cards.insert(*player, HashSet::new());
                     for _ in 0..self.cards_per_player {
@@ -68,35 +133,33 @@ impl GameRules for DefaultRules {
                 state.card_stack = cards.clone();
                 state.trump = state.card_stack.last().map(|x| x.suite.clone());
 
-                // TODO
-                state.target_player = players.get(1).cloned();
-                state.attack_player = players.get(0).cloned();
-                state.neighbor_player = players.get(2).cloned();
+                // TODO the player with the lowest trump should start
+                match players.first() {
+                    Some(first) => assign_roles(state, players, *first),
+                    None => return Err(durak_error!(NotEnoughPlayers, "Not enough players.")),
+                }
             }
             GameAction::PutCard(card, stack_ind) => {
                 let target = match state.target_player {
                     Some(player) => player,
-                    None => return Err(durak_error!(GameError, "No target player.")),
+                    None => return Err(durak_error!(InvalidGameState, "No target player.")),
                 };
                 let attack = match state.attack_player {
                     Some(player) => player,
-                    None => return Err(durak_error!(GameError, "No attacking player.")),
-                };
-                let neighbor = match state.neighbor_player {
-                    Some(player) => player,
-                    None => return Err(durak_error!(GameError, "No attacking player.")),
+                    None => return Err(durak_error!(InvalidGameState, "No attacking player.")),
                 };
+                let neighbor = state.neighbor_player;
                 let target_num_cards = match state.player_cards.clone().get(&target) {
                     Some(cards) => cards.len(),
-                    None => return Err(durak_error!(GameError, "Target player cards not found.")),
+                    None => return Err(durak_error!(InvalidGameState, "Target player cards not found.")),
                 };
                 match state.player_cards.get_mut(origin) {
                     Some(cards) => {
                         match stack_ind {
                             Some(ind) => {
-                                if !(target == *origin) {
+                                if target != *origin {
                                     return Err(durak_error!(
-                                        GameError,
+                                        NotYourTurn,
                                         "Only target player can defend."
                                     ));
                                 }
@@ -108,14 +171,14 @@ impl GameRules for DefaultRules {
                                                     match card.better_as(a.clone(), trump.clone()) {
                                                         Some(false) => {
                                                             return Err(durak_error!(
-                                                                GameError,
+                                                                CardTooLow,
                                                                 "Defending is only possible with better card."
                                                             ))
                                                         }
                                                         Some(true) => {}
                                                         None => {
                                                             return Err(durak_error!(
-                                                                GameError,
+                                                                CardNotMatching,
                                                                 "Defending is only possible with matching card."
                                                             ))
                                                         }
@@ -123,82 +186,250 @@ impl GameRules for DefaultRules {
                                                 }
                                                 None => {
                                                     return Err(durak_error!(
-                                                        GameError,
+                                                        InvalidGameState,
                                                         "No trump suite defined."
                                                     ))
                                                 }
                                             }
                                             if !cards.remove(&card) {
                                                 return Err(
-                                                    durak_error!(GameError, "Card not found."),
+                                                    durak_error!(CardNotInHand, "Card not found."),
                                                 );
                                             }
                                             *stack = (a, Some(card.clone()));
                                         } else {
                                             return Err(durak_error!(
-                                                GameError,
+                                                StackAlreadyDefended,
                                                 "Card already defended."
                                             ));
                                         }
                                     }
-                                    None => return Err(durak_error!(GameError, "Stack not found.")),
+                                    None => return Err(durak_error!(StackNotFound, "Stack not found.")),
                                 }
                             }
                             None => {
-                                println!("{:016X} {:016X} {:016X}", origin, attack, neighbor);
-                                if state.table_stacks.is_empty() && !(attack == *origin) {
+                                log_trace!(
+                                    client = *origin,
+                                    attack = attack,
+                                    neighbor = neighbor;
+                                    "Attack with {:?}.",
+                                    card
+                                );
+                                if state.table_stacks.is_empty() && attack != *origin {
                                     return Err(durak_error!(
-                                        GameError,
+                                        NotYourTurn,
                                         "Only attacking player can start."
                                     ));
                                 }
-                                if !((attack == *origin) || (neighbor == *origin)) {
+                                if !((attack == *origin) || (neighbor == Some(*origin))) {
                                     return Err(durak_error!(
-                                        GameError,
+                                        NotYourTurn,
                                         "Only attacking player and neighbor can start a new stack."
                                     ));
                                 }
                                 let stacks = state.table_stacks.clone();
-                                if !stacks.is_empty() {
-                                    if stacks.iter().fold(true, |mut acc, &(ref a, ref b)| {
-                                        acc = acc && !(card.value == a.value);
+                                if !stacks.is_empty() &&
+                                    stacks.iter().fold(true, |mut acc, (a, b)| {
+                                        acc = acc && card.value != a.value;
                                         if let Some(ref x) = *b {
-                                            acc = acc && !(card.value == x.value);
+                                            acc = acc && card.value != x.value;
                                         }
                                         acc
                                     })
-                                    {
-                                        println!("NOT FOUND {:?} {:?}", card, stacks);
-                                        return Err(durak_error!(
-                                            GameError,
-                                            "Attacking is only possible with existing card values."
-                                        ));
-                                    }
-                                    println!("FOUND {:?} {:?}", card, stacks);
+                                {
+                                    log_debug!(
+                                        client = *origin;
+                                        "Card value of {:?} not on table {:?}.",
+                                        card,
+                                        stacks
+                                    );
+                                    return Err(durak_error!(
+                                        CardValueNotOnTable,
+                                        "Attacking is only possible with existing card values."
+                                    ));
                                 }
                                 if state
                                     .table_stacks
                                     .iter()
-                                    .filter(|&&(_, ref b)| b.is_none())
+                                    .filter(|(_, b)| b.is_none())
                                     .count() >=
-                                    target_num_cards
+                                    target_num_cards ||
+                                    state.table_stacks.len() >= MAX_STACKS
                                 {
                                     return Err(durak_error!(
-                                        GameError,
+                                        TooManyStacks,
                                         "No more stacks than cards allowed"
                                     ));
                                 }
                                 if !cards.remove(&card) {
-                                    return Err(durak_error!(GameError, "Card not found."));
+                                    return Err(durak_error!(CardNotInHand, "Card not found."));
                                 }
                                 state.table_stacks.push((card.clone(), None));
                             }
                         }
                     }
-                    None => return Err(durak_error!(GameError, "Player not found.")),
+                    None => return Err(durak_error!(PlayerNotFound, "Player not found.")),
+                }
+                if let Some(revealed) = state.revealed.get_mut(origin) {
+                    revealed.remove(&card);
+                }
+                state.passed.clear();
+                if round_complete(state) {
+                    self.end_round(state, players, false);
+                }
+            }
+            GameAction::Take => {
+                if state.target_player != Some(*origin) {
+                    return Err(durak_error!(NotYourTurn, "Only target player can take."));
+                }
+                if state.table_stacks.iter().all(|(_, b)| b.is_some()) {
+                    return Err(durak_error!(NothingToTake, "No undefended cards to take."));
+                }
+                let stacks: Vec<(Card, Option<Card>)> = state.table_stacks.drain(..).collect();
+                let revealed = state.revealed.entry(*origin).or_default();
+                if let Some(cards) = state.player_cards.get_mut(origin) {
+                    for (a, b) in stacks {
+                        for card in Some(a).into_iter().chain(b) {
+                            revealed.insert(card.clone());
+                            cards.insert(card);
+                        }
+                    }
+                }
+                log_trace!(client = *origin; "Cards taken.");
+                self.end_round(state, players, true);
+            }
+            GameAction::Pass => {
+                if !(state.attack_player == Some(*origin) ||
+                         state.neighbor_player == Some(*origin))
+                {
+                    return Err(durak_error!(
+                        NotYourTurn,
+                        "Only attacking player and neighbor can pass."
+                    ));
+                }
+                if state.table_stacks.is_empty() {
+                    return Err(durak_error!(
+                        CannotPass,
+                        "Passing is only possible after attacking."
+                    ));
+                }
+                if state.passed.contains(origin) {
+                    return Err(durak_error!(CannotPass, "Already passed."));
+                }
+                state.passed.push(*origin);
+                if round_complete(state) {
+                    self.end_round(state, players, false);
                 }
             }
         }
         Ok(state.clone())
     }
 }
+
+impl DefaultRules {
+    /// Clears the table, refills the hands from the talon and hands the
+    /// attack on. A successful defender attacks next, after taking the
+    /// player behind the defender does.
+    fn end_round(&self, state: &mut GameState, players: &[ClientHash], taken: bool) {
+        let (attack, target) = match (state.attack_player, state.target_player) {
+            (Some(attack), Some(target)) => (attack, target),
+            _ => return,
+        };
+        let discarded: Vec<(Card, Option<Card>)> = state.table_stacks.drain(..).collect();
+        for (a, b) in discarded {
+            state.discard.push(a);
+            state.discard.extend(b);
+        }
+        state.passed.clear();
+
+        // Attackers draw first, starting with the attacking player, the
+        // defender draws last.
+        let start = players.iter().position(|x| *x == attack).unwrap_or(0);
+        let mut order: Vec<ClientHash> = (0..players.len())
+            .map(|i| players[(start + i) % players.len()])
+            .filter(|x| *x != target)
+            .collect();
+        order.push(target);
+        for player in order {
+            if let Some(cards) = state.player_cards.get_mut(&player) {
+                while cards.len() < self.cards_per_player && !state.card_stack.is_empty() {
+                    let card = state.card_stack.remove(0);
+                    // Everybody saw the trump card at the bottom of the talon.
+                    if state.card_stack.is_empty() {
+                        state.revealed.entry(player).or_default().insert(card.clone());
+                    }
+                    cards.insert(card);
+                }
+            }
+        }
+
+        if state.card_stack.is_empty() {
+            for player in players {
+                let empty = state.player_cards.get(player).map(|x| x.is_empty()).unwrap_or(true);
+                if empty && !state.finished.contains(player) {
+                    state.finished.push(*player);
+                }
+            }
+        }
+        let active: Vec<ClientHash> = players
+            .iter()
+            .filter(|x| !state.finished.contains(x))
+            .cloned()
+            .collect();
+        if active.len() <= 1 {
+            state.game_over = true;
+            state.durak = active.first().cloned();
+            state.attack_player = None;
+            state.target_player = None;
+            state.neighbor_player = None;
+            log_debug!(durak = state.durak; "Game over.");
+            return;
+        }
+        let next = if taken || state.finished.contains(&target) {
+            next_active(state, players, target)
+        } else {
+            target
+        };
+        assign_roles(state, players, next);
+        log_trace!(attack = next, taken = taken; "Round finished.");
+    }
+}
+
+/// All stacks are defended and nobody is able or willing to add another.
+fn round_complete(state: &GameState) -> bool {
+    if state.table_stacks.is_empty() || state.table_stacks.iter().any(|(_, b)| b.is_none()) {
+        return false;
+    }
+    let target_empty = state
+        .target_player
+        .and_then(|x| state.player_cards.get(&x))
+        .map(|x| x.is_empty())
+        .unwrap_or(true);
+    if target_empty || state.table_stacks.len() >= MAX_STACKS {
+        return true;
+    }
+    [state.attack_player, state.neighbor_player]
+        .iter()
+        .flatten()
+        .all(|x| {
+            state.passed.contains(x) ||
+                state.player_cards.get(x).map(|x| x.is_empty()).unwrap_or(true)
+        })
+}
+
+/// The next player after `player` who is still in the game.
+fn next_active(state: &GameState, players: &[ClientHash], player: ClientHash) -> ClientHash {
+    let start = players.iter().position(|x| *x == player).unwrap_or(0);
+    (1..players.len() + 1)
+        .map(|i| players[(start + i) % players.len()])
+        .find(|x| !state.finished.contains(x))
+        .unwrap_or(player)
+}
+
+fn assign_roles(state: &mut GameState, players: &[ClientHash], attack: ClientHash) {
+    let target = next_active(state, players, attack);
+    let neighbor = next_active(state, players, target);
+    state.attack_player = Some(attack);
+    state.target_player = Some(target);
+    state.neighbor_player = if neighbor != attack { Some(neighbor) } else { None };
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::thread;
+use std::time::Duration;
+use bot::*;
+use game::*;
+use network::*;
+use rules::*;
+
+/// Upper bound of actions in a single game, longer games are aborted.
+const MAX_GAME_ACTIONS: usize = 10_000;
+
+/// z value of a two-sided 95% confidence interval.
+const Z95: f64 = 1.96;
+
+/// Plays bots against each other without a room or any sockets.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    pub bots: Vec<BotLevel>,
+    pub games: usize,
+    /// Game `i` is dealt with seed `seed + i`.
+    pub seed: usize,
+    pub cards_per_player: usize,
+    pub bot_config: BotConfig,
+}
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub games: usize,
+    pub seed: usize,
+    pub aborted: usize,
+    pub draws: usize,
+    /// Number of actions of every finished game.
+    pub lengths: Vec<usize>,
+    pub bots: Vec<BotStats>,
+}
+
+#[derive(Debug, Clone)]
+pub struct BotStats {
+    pub level: BotLevel,
+    pub games: usize,
+    /// Games the bot finished first.
+    pub wins: usize,
+    pub durak: usize,
+}
+
+impl Simulation {
+    pub fn new(bots: Vec<BotLevel>) -> Simulation {
+        Simulation {
+            bots,
+            games: 1000,
+            seed: 1,
+            cards_per_player: 5,
+            bot_config: BotConfig {
+                budget: Some(Duration::from_millis(10)),
+                ..BotConfig::default()
+            },
+        }
+    }
+
+    /// Plays all games. Seats rotate from game to game so that no bot keeps
+    /// the advantage of attacking first.
+    pub fn run(&self) -> Result<Report> {
+        if self.bots.len() < 2 {
+            return Err(DurakError::new(ErrorCode::NotEnoughPlayers, "Not enough players."));
+        }
+        let mut report = Report {
+            games: self.games,
+            seed: self.seed,
+            aborted: 0,
+            draws: 0,
+            lengths: Vec::new(),
+            bots: self
+                .bots
+                .iter()
+                .map(|level| BotStats {
+                    level: level.clone(),
+                    games: 0,
+                    wins: 0,
+                    durak: 0,
+                })
+                .collect(),
+        };
+        for game in 0..self.games {
+            let rules = DefaultRules::with_cards_per_player(self.cards_per_player)
+                .seeded(self.seed.wrapping_add(game));
+            // Seat i is played by bot (i + game) % n, its hash is the bot index.
+            let count = self.bots.len();
+            let players: Vec<ClientHash> =
+                (0..count).map(|i| ((i + game) % count) as ClientHash).collect();
+            let mut bots: Vec<Box<dyn Bot>> = Vec::new();
+            for level in &self.bots {
+                bots.push(level.create(&rules, &self.bot_config)?);
+            }
+            let state = match play(&rules, &players, &mut bots)? {
+                Some((state, length)) => {
+                    report.lengths.push(length);
+                    state
+                }
+                None => {
+                    report.aborted += 1;
+                    continue;
+                }
+            };
+            for stats in &mut report.bots {
+                stats.games += 1;
+            }
+            if let Some(&winner) = state.finished.first() {
+                report.bots[winner as usize].wins += 1;
+            }
+            match state.durak {
+                Some(durak) => report.bots[durak as usize].durak += 1,
+                None => report.draws += 1,
+            }
+            log_debug!(game = game, durak = state.durak; "Simulated game finished.");
+        }
+        if self.games > 0 && report.aborted == self.games {
+            return Err(DurakError::new(
+                ErrorCode::InvalidGameState,
+                format!("All {} games were aborted.", self.games),
+            ));
+        }
+        Ok(report)
+    }
+}
+
+/// Plays a single game to its end. Returns the final state and the number
+/// of actions, or `None` if the game did not finish in time.
+fn play<T: GameRules>(
+    rules: &T,
+    players: &[ClientHash],
+    bots: &mut [Box<dyn Bot>],
+) -> Result<Option<(GameState, usize)>> {
+    let mut state = GameState::new();
+    rules.apply(&players[0], &mut state, players, GameAction::DealCards)?;
+    for length in 0..MAX_GAME_ACTIONS {
+        let (actor, actions) = match next_actor(rules, &state, players) {
+            Some(turn) => turn,
+            None => return Ok(Some((state, length))),
+        };
+        let bot = &mut bots[actor as usize];
+        let action = loop {
+            match bot.choose(&actor, &state.view(&actor), players, &actions) {
+                Some(action) => break action,
+                None if bot.pending() => thread::sleep(Duration::from_millis(1)),
+                None => break actions[0].clone(),
+            }
+        };
+        rules.apply(&actor, &mut state, players, action)?;
+    }
+    Ok(None)
+}
+
+impl Report {
+    pub fn average_length(&self) -> f64 {
+        if self.lengths.is_empty() {
+            return 0.0;
+        }
+        self.lengths.iter().sum::<usize>() as f64 / self.lengths.len() as f64
+    }
+
+    /// Half width of the 95% confidence interval of the average length.
+    pub fn length_margin(&self) -> f64 {
+        let n = self.lengths.len() as f64;
+        if n < 2.0 {
+            return 0.0;
+        }
+        let mean = self.average_length();
+        let variance = self.lengths.iter().map(|&x| (x as f64 - mean).powi(2)).sum::<f64>() /
+            (n - 1.0);
+        Z95 * (variance / n).sqrt()
+    }
+}
+
+/// Wilson score interval of `successes` out of `n` at 95% confidence.
+pub fn wilson_interval(successes: usize, n: usize) -> (f64, f64) {
+    if n == 0 {
+        return (0.0, 1.0);
+    }
+    let n = n as f64;
+    let p = successes as f64 / n;
+    let z2 = Z95 * Z95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = Z95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    (center - margin, center + margin)
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "games {}  seed {}  aborted {}  draws {}",
+            self.games,
+            self.seed,
+            self.aborted,
+            self.draws
+        )?;
+        writeln!(
+            f,
+            "average length {:.1} ± {:.1} actions",
+            self.average_length(),
+            self.length_margin()
+        )?;
+        writeln!(
+            f,
+            "{:<3} {:<10} {:>6} {:>7} {:>15} {:>7} {:>15}",
+            "#",
+            "bot",
+            "games",
+            "win",
+            "95% ci",
+            "durak",
+            "95% ci"
+        )?;
+        for (i, stats) in self.bots.iter().enumerate() {
+            let games = stats.games.max(1) as f64;
+            let (win_low, win_high) = wilson_interval(stats.wins, stats.games);
+            let (durak_low, durak_high) = wilson_interval(stats.durak, stats.games);
+            writeln!(
+                f,
+                "{:<3} {:<10} {:>6} {:>6.1}% {:>15} {:>6.1}% {:>15}",
+                i,
+                stats.level,
+                stats.games,
+                100.0 * stats.wins as f64 / games,
+                format!("[{:.1}, {:.1}]", 100.0 * win_low, 100.0 * win_high),
+                100.0 * stats.durak as f64 / games,
+                format!("[{:.1}, {:.1}]", 100.0 * durak_low, 100.0 * durak_high)
+            )?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,306 @@
+//! Statistics and ratings of registered players. They are kept in a plain
+//! text file with the rating, the number of abandoned games and one record
+//! per variant and number of players for every account:
+//!
+//! ```text
+//! rating Kekse 1516.0
+//! abandoned Kekse 2
+//! record Kekse podkidnoy-5 3 12 4 25
+//! ```
+//!
+//! A record holds the games played, the games lost as durak and the sum of
+//! the finish positions.
+
+use std::collections::hash_map::Values;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::path::PathBuf;
+use network::*;
+
+// TODO import
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Rating of players who never finished a game.
+pub const DEFAULT_RATING: f64 = 1500.0;
+
+/// Most rating points a game against a single opponent can change.
+const K_FACTOR: f64 = 32.0;
+
+/// Games of one player in one variant with a fixed number of players.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Record {
+    pub games: usize,
+    /// Games lost as durak.
+    pub durak: usize,
+    /// Sum of the finish positions, 1 for the first player out of cards.
+    pub places: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlayerStats {
+    pub name: String,
+    pub rating: f64,
+    /// Games left, disconnected from or timed out of before they ended.
+    pub abandoned: usize,
+    /// Records by variant and number of players.
+    pub records: BTreeMap<(String, usize), Record>,
+}
+
+/// Finish position of a player in a game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Placing {
+    pub name: String,
+    /// 1 for the first player out of cards, players who finished at the same
+    /// time share the position.
+    pub place: usize,
+    pub durak: bool,
+    /// Only registered players are rated and get statistics.
+    pub registered: bool,
+}
+
+/// Outcome of a finished game.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameResult {
+    /// End of the game in seconds since the Unix epoch.
+    pub time: u64,
+    pub variant: String,
+    pub placings: Vec<Placing>,
+}
+
+/// Statistics of all registered players, saved to `path` after every game
+/// if one is given.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    path: Option<PathBuf>,
+    players: HashMap<String, PlayerStats>,
+}
+
+impl Record {
+    /// Share of the games not lost as durak.
+    pub fn win_rate(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        (self.games - self.durak) as f64 / self.games as f64
+    }
+
+    pub fn average_place(&self) -> f64 {
+        if self.games == 0 {
+            return 0.0;
+        }
+        self.places as f64 / self.games as f64
+    }
+
+    fn add(&mut self, other: &Record) {
+        self.games += other.games;
+        self.durak += other.durak;
+        self.places += other.places;
+    }
+}
+
+impl PlayerStats {
+    pub fn new<S: Into<String>>(name: S) -> PlayerStats {
+        PlayerStats {
+            name: name.into(),
+            rating: DEFAULT_RATING,
+            abandoned: 0,
+            records: BTreeMap::new(),
+        }
+    }
+
+    /// All records summed up.
+    pub fn total(&self) -> Record {
+        let mut total = Record::default();
+        for record in self.records.values() {
+            total.add(record);
+        }
+        total
+    }
+}
+
+impl Stats {
+    /// Statistics kept in memory only.
+    pub fn new() -> Stats {
+        Stats::default()
+    }
+
+    /// Loads the statistics stored at `path`. A missing file is created
+    /// after the first game.
+    pub fn open<P: Into<PathBuf>>(path: P) -> Result<Stats> {
+        let path = path.into();
+        let mut stats = Stats {
+            path: None,
+            players: HashMap::new(),
+        };
+        if path.exists() {
+            for line in BufReader::new(File::open(&path)?).lines() {
+                let line = line?;
+                if !line.trim().is_empty() {
+                    stats.parse_line(&line)?;
+                }
+            }
+        }
+        stats.path = Some(path);
+        Ok(stats)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&PlayerStats> {
+        self.players.get(name)
+    }
+
+    pub fn players(&self) -> Values<'_, String, PlayerStats> {
+        self.players.values()
+    }
+
+    pub fn rating(&self, name: &str) -> f64 {
+        self.players.get(name).map(|x| x.rating).unwrap_or(DEFAULT_RATING)
+    }
+
+    pub fn abandoned(&self, name: &str) -> usize {
+        self.players.get(name).map(|x| x.abandoned).unwrap_or(0)
+    }
+
+    /// Counts a game the registered player `name` abandoned.
+    pub fn abandon(&mut self, name: &str) -> Result<()> {
+        self.entry(name).abandoned += 1;
+        self.save()
+    }
+
+    /// Counts a finished game for the registered players taking part and
+    /// updates their ratings. Guests count as opponents with the default
+    /// rating.
+    pub fn record(&mut self, result: &GameResult) -> Result<()> {
+        if !result.placings.iter().any(|x| x.registered) {
+            return Ok(());
+        }
+        let ratings: Vec<f64> = result
+            .placings
+            .iter()
+            .map(|x| if x.registered { self.rating(&x.name) } else { DEFAULT_RATING })
+            .collect();
+        let places: Vec<usize> = result.placings.iter().map(|x| x.place).collect();
+        let ratings = rate(&ratings, &places);
+        let count = result.placings.len();
+        for (placing, rating) in result.placings.iter().zip(ratings) {
+            if !placing.registered {
+                continue;
+            }
+            let player = self
+                .players
+                .entry(placing.name.clone())
+                .or_insert_with(|| PlayerStats::new(placing.name.clone()));
+            player.rating = rating;
+            let record = player.records.entry((result.variant.clone(), count)).or_default();
+            record.games += 1;
+            record.places += placing.place;
+            if placing.durak {
+                record.durak += 1;
+            }
+        }
+        self.save()
+    }
+
+    fn parse_line(&mut self, line: &str) -> Result<()> {
+        let parts: Vec<&str> = line.split_whitespace().collect();
+        match parts.first() {
+            Some(&"rating") if parts.len() == 3 => {
+                let rating = parts[2].parse()?;
+                self.entry(parts[1]).rating = rating;
+            }
+            Some(&"abandoned") if parts.len() == 3 => {
+                let abandoned = parts[2].parse()?;
+                self.entry(parts[1]).abandoned = abandoned;
+            }
+            Some(&"record") if parts.len() == 7 => {
+                let record = Record {
+                    games: parts[4].parse()?,
+                    durak: parts[5].parse()?,
+                    places: parts[6].parse()?,
+                };
+                let key = (parts[2].to_string(), parts[3].parse()?);
+                self.entry(parts[1]).records.insert(key, record);
+            }
+            _ => return Err(invalid_line()),
+        }
+        Ok(())
+    }
+
+    fn entry(&mut self, name: &str) -> &mut PlayerStats {
+        self.players.entry(name.into()).or_insert_with(|| PlayerStats::new(name))
+    }
+
+    /// Writes all statistics to a temporary file that replaces the old one.
+    fn save(&self) -> Result<()> {
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(()),
+        };
+        let temporary = path.with_extension("tmp");
+        {
+            let mut file = File::create(&temporary)?;
+            let mut names: Vec<&String> = self.players.keys().collect();
+            names.sort();
+            for name in names {
+                let player = &self.players[name];
+                writeln!(file, "rating {} {:.1}", name, player.rating)?;
+                if player.abandoned > 0 {
+                    writeln!(file, "abandoned {} {}", name, player.abandoned)?;
+                }
+                for (&(ref variant, players), record) in &player.records {
+                    writeln!(
+                        file,
+                        "record {} {} {} {} {} {}",
+                        name,
+                        variant,
+                        players,
+                        record.games,
+                        record.durak,
+                        record.places
+                    )?;
+                }
+            }
+            file.sync_all()?;
+        }
+        fs::rename(&temporary, path)?;
+        Ok(())
+    }
+}
+
+/// Multi-player Elo: every player is rated against each opponent as if they
+/// played a game of two, the better finish position wins. The change is
+/// divided by the number of opponents so that large games do not count more
+/// than small ones.
+pub fn rate(ratings: &[f64], places: &[usize]) -> Vec<f64> {
+    let opponents = ratings.len().saturating_sub(1).max(1) as f64;
+    ratings
+        .iter()
+        .zip(places)
+        .enumerate()
+        .map(|(i, (rating, place))| {
+            let change: f64 = ratings
+                .iter()
+                .zip(places)
+                .enumerate()
+                .filter(|&(j, _)| j != i)
+                .map(|(_, (other, other_place))| {
+                    let expected = 1.0 / (1.0 + 10f64.powf((other - rating) / 400.0));
+                    let score = match place.cmp(other_place) {
+                        ::std::cmp::Ordering::Less => 1.0,
+                        ::std::cmp::Ordering::Equal => 0.5,
+                        ::std::cmp::Ordering::Greater => 0.0,
+                    };
+                    score - expected
+                })
+                .sum();
+            rating + K_FACTOR * change / opponents
+        })
+        .collect()
+}
+
+fn invalid_line() -> DurakError {
+    durak_error!(InvalidConfig, "Invalid statistics line.")
+}
@@ -0,0 +1,556 @@
+use std::fmt::Debug;
+use std::fs;
+use std::io;
+use std::io::prelude::*;
+use std::io::{BufReader, BufWriter};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+use rand::random;
+use game::*;
+use network::*;
+use rules::*;
+
+macro_rules! durak_error {
+    ($c:ident, $x:expr) => (DurakError::new(ErrorCode::$c, $x))
+}
+
+/// Server side of a client connection: answers go out, commands come in.
+pub type ClientChannel<T> = DuplexChannel<Answer<T>, Command<T>>;
+
+/// Client side of a connection, as handed out by the in-memory transport.
+pub type RemoteChannel<T> = DuplexChannel<Command<T>, Answer<T>>;
+
+/// Announcement of a newly connected client.
+pub type NewClient<T> = (ClientHash, ClientChannel<T>);
+
+/// Source of client connections for a `Server`.
+///
+/// A transport announces every new client with a fresh `ClientHash` and the
+/// channel the server uses to receive its commands and send its answers.
+pub trait Transport<T: GameRules + Clone + Send>: Send {
+    fn start(self: Box<Self>, clients: mpsc::Sender<NewClient<T>>)
+        -> Result<()>;
+}
+
+/// Byte stream speaking the line based text protocol.
+pub trait Connection: Read + Write + Send + Sized + 'static {
+    fn try_clone(&self) -> io::Result<Self>;
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()>;
+}
+
+pub struct TcpTransport {
+    listener: TcpListener,
+    timeout: Option<Duration>,
+}
+
+pub struct UnixTransport {
+    listener: UnixListener,
+    path: PathBuf,
+    timeout: Option<Duration>,
+}
+
+pub struct MemoryTransport<T: GameRules + Clone + Send> {
+    rx: mpsc::Receiver<NewClient<T>>,
+}
+
+#[derive(Clone)]
+pub struct MemoryConnector<T: GameRules + Clone + Send> {
+    tx: mpsc::Sender<NewClient<T>>,
+}
+
+impl Connection for TcpStream {
+    fn try_clone(&self) -> io::Result<TcpStream> {
+        TcpStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        TcpStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl Connection for UnixStream {
+    fn try_clone(&self) -> io::Result<UnixStream> {
+        UnixStream::try_clone(self)
+    }
+
+    fn set_read_timeout(&self, timeout: Option<Duration>) -> io::Result<()> {
+        UnixStream::set_read_timeout(self, timeout)
+    }
+}
+
+impl TcpTransport {
+    pub fn bind<S: ToSocketAddrs>(address: S) -> Result<TcpTransport> {
+        Ok(TcpTransport {
+            listener: TcpListener::bind(address)?,
+            timeout: None,
+        })
+    }
+
+    /// Disconnects clients that did not send anything for `timeout`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl<T: GameRules + Debug + Clone + Send + 'static> Transport<T> for TcpTransport {
+    fn start(
+        self: Box<Self>,
+        clients: mpsc::Sender<NewClient<T>>,
+    ) -> Result<()> {
+        let listener = self.listener;
+        let timeout = self.timeout;
+        thread::spawn(move || accept(listener.incoming(), timeout, &clients));
+        Ok(())
+    }
+}
+
+impl UnixTransport {
+    /// Binds to the socket at `path`. A stale socket file left behind by a
+    /// previous server is replaced, a socket still in use or any other file
+    /// is an error.
+    pub fn bind<P: AsRef<Path>>(path: P) -> Result<UnixTransport> {
+        UnixTransport::bind_with_mode(path, None)
+    }
+
+    /// Disconnects clients that did not send anything for `timeout`.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+
+    /// Binds like `bind`, but the socket only becomes reachable at `path`
+    /// once it has the permissions `mode`.
+    pub fn bind_with_mode<P: AsRef<Path>>(path: P, mode: Option<u32>) -> Result<UnixTransport> {
+        let path = path.as_ref();
+        if let Ok(metadata) = fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(durak_error!(
+                    IoError,
+                    format!("{} exists and is not a socket.", path.display())
+                ));
+            }
+            if UnixStream::connect(path).is_ok() {
+                return Err(durak_error!(
+                    IoError,
+                    format!("Socket {} already in use.", path.display())
+                ));
+            }
+            fs::remove_file(path)?;
+        }
+        let listener = match mode {
+            Some(mode) => bind_private(path, mode)?,
+            None => UnixListener::bind(path)?,
+        };
+        Ok(UnixTransport {
+            listener,
+            path: path.to_path_buf(),
+            timeout: None,
+        })
+    }
+
+    /// Restricts access to the socket file, e.g. `0o660` to only allow the
+    /// owning user and group.
+    pub fn set_mode(&self, mode: u32) -> Result<()> {
+        fs::set_permissions(&self.path, fs::Permissions::from_mode(mode))?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Binds the socket in a directory only the server may enter and moves it
+/// to `path` after applying `mode`, so that nobody can connect before the
+/// permissions are in place.
+fn bind_private(path: &Path, mode: u32) -> Result<UnixListener> {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let directory = path.with_file_name(format!(".{}.{}", name, process::id()));
+    fs::DirBuilder::new().mode(0o700).create(&directory)?;
+    let temporary = directory.join("socket");
+    let result = UnixListener::bind(&temporary).and_then(|listener| {
+        fs::set_permissions(&temporary, fs::Permissions::from_mode(mode))?;
+        fs::rename(&temporary, path)?;
+        Ok(listener)
+    });
+    let _ = fs::remove_file(&temporary);
+    let _ = fs::remove_dir(&directory);
+    Ok(result?)
+}
+
+impl<T: GameRules + Debug + Clone + Send + 'static> Transport<T> for UnixTransport {
+    fn start(
+        self: Box<Self>,
+        clients: mpsc::Sender<NewClient<T>>,
+    ) -> Result<()> {
+        let listener = self.listener;
+        let timeout = self.timeout;
+        thread::spawn(move || accept(listener.incoming(), timeout, &clients));
+        Ok(())
+    }
+}
+
+impl<T: GameRules + Clone + Send> MemoryTransport<T> {
+    /// Creates an in-process transport together with the connector used to
+    /// attach clients to it.
+    pub fn new() -> (MemoryTransport<T>, MemoryConnector<T>) {
+        let (tx, rx) = mpsc::channel();
+        (MemoryTransport { rx }, MemoryConnector { tx })
+    }
+}
+
+impl<T: GameRules + Clone + Send + 'static> Transport<T> for MemoryTransport<T> {
+    fn start(
+        self: Box<Self>,
+        clients: mpsc::Sender<NewClient<T>>,
+    ) -> Result<()> {
+        let rx = self.rx;
+        thread::spawn(move || for client in rx {
+            if clients.send(client).is_err() {
+                break;
+            }
+        });
+        Ok(())
+    }
+}
+
+impl<T: GameRules + Clone + Send> MemoryConnector<T> {
+    /// Attaches a new client and returns its hash and the channel to send
+    /// commands and receive answers on.
+    pub fn connect(&self) -> Result<(ClientHash, RemoteChannel<T>)> {
+        let id: ClientHash = random();
+        let (remote_channel, local_channel) = DuplexChannel::new();
+        self.tx.send((id, remote_channel))?;
+        Ok((id, local_channel))
+    }
+}
+
+fn accept<T, C, I>(incoming: I, timeout: Option<Duration>, clients: &mpsc::Sender<NewClient<T>>)
+where
+    T: GameRules + Debug + Clone + Send + 'static,
+    C: Connection,
+    I: Iterator<Item = io::Result<C>>,
+{
+    for stream in incoming.flatten() {
+        let id: ClientHash = random();
+        if let Err(e) = stream.set_read_timeout(timeout) {
+            log_warn!(client = id; "Unable to set timeout: {}", e);
+            continue;
+        }
+        match serve(id, stream) {
+            Ok(channel) => {
+                if clients.send((id, channel)).is_err() {
+                    break;
+                }
+            }
+            Err(e) => log_warn!(client = id; "Unable to serve connection: {}", e),
+        }
+    }
+}
+
+/// Spawns the reader and writer threads for a text protocol connection and
+/// returns the channel connected to them.
+pub fn serve<T, C>(id: ClientHash, connection: C) -> Result<ClientChannel<T>>
+where
+    T: GameRules + Debug + Clone + Send + 'static,
+    C: Connection,
+{
+    let (remote_channel, local_channel) = DuplexChannel::new();
+    let (tx, rx) = local_channel.split();
+    let local_stream = connection.try_clone()?;
+    thread::spawn(move || {
+        let mut reader = BufReader::new(local_stream);
+        loop {
+            let mut line = String::new();
+            match reader.read_line(&mut line) {
+                Ok(0) => break,
+                Ok(_) => {
+                    let command = match Command::parse(line) {
+                        Ok(cmd) => cmd,
+                        Err(e) => {
+                            log_debug!(client = id; "Invalid command: {}", e);
+                            Command::Answer(Box::new(Answer::Error(e)))
+                        }
+                    };
+                    if tx.send(command).is_err() {
+                        break;
+                    }
+                }
+                Err(ref e)
+                    if e.kind() == io::ErrorKind::WouldBlock ||
+                           e.kind() == io::ErrorKind::TimedOut => {
+                    log_info!(client = id; "Connection timed out.");
+                    break;
+                }
+                Err(e) => log_debug!(client = id; "Unable to read line: {}", e),
+            }
+        }
+    });
+    thread::spawn(move || {
+        let mut writer = BufWriter::new(connection);
+        for answer in rx {
+            if write_answer(&mut writer, id, answer).is_err() {
+                break;
+            }
+            let _ = writer.flush();
+        }
+    });
+    Ok(remote_channel)
+}
+
+/// Renders an answer in the text protocol as seen by client `id`. Lists,
+/// states, settings and statistics are blocks of lines closed by an empty
+/// line, errors and notifications like `chat` or `timer` a single line.
+pub fn write_answer<T, W>(writer: &mut W, id: ClientHash, answer: Answer<T>) -> io::Result<()>
+where
+    T: GameRules + Clone + Send,
+    W: Write,
+{
+    match answer {
+        Answer::PlayerList(list) => {
+            for (hash, player) in list {
+                match player.rating {
+                    Some(rating) => {
+                        writeln!(writer, "{:016X} {} {:.0}", hash, player.name, rating)?
+                    }
+                    None => writeln!(writer, "{:016X} {}", hash, player.name)?,
+                }
+            }
+            writeln!(writer)?;
+        }
+        Answer::PlayerState(hash, player) => {
+            writeln!(writer, "hash  {:016X}", hash)?;
+            writeln!(writer, "name  {}", player.name)?;
+            writeln!(writer, "lang  {}", player.lang)?;
+            if let Some(table) = player.table {
+                writeln!(writer, "table {:016X}", table)?;
+            }
+            if let Some(table) = player.watching {
+                writeln!(writer, "watch {:016X}", table)?;
+            }
+            writeln!(writer, "abandoned {}", player.abandoned)?;
+            if player.registered {
+                writeln!(writer, "account yes")?;
+            }
+            if let Some(rating) = player.rating {
+                writeln!(writer, "rating {:.0}", rating)?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::TableList(list) => {
+            for (tablehash, table) in list {
+                writeln!(
+                    writer,
+                    "{:016X} {} {} {} {} {} {}",
+                    tablehash,
+                    table.players.len(),
+                    table.min_players,
+                    table.max_players,
+                    table.get_state(),
+                    table.name,
+                    table.get_access()
+                )?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::TableSettings(tablehash, table) => {
+            writeln!(writer, "settings {:016X}", tablehash)?;
+            writeln!(writer, "name {}", table.name)?;
+            if let Some(owner) = table.owner {
+                writeln!(writer, "owner {:016X}", owner)?;
+            }
+            writeln!(
+                writer,
+                "players {} {} {}",
+                table.players.len(),
+                table.min_players,
+                table.max_players
+            )?;
+            writeln!(writer, "locked {}", if table.locked { "yes" } else { "no" })?;
+            writeln!(writer, "spectators {}", if table.spectating { "on" } else { "off" })?;
+            writeln!(writer, "password {}", if table.password.is_some() { "yes" } else { "no" })?;
+            if let Some(ref invite) = table.invite {
+                writeln!(writer, "invite {}", invite)?;
+            }
+            writeln!(writer, "abandon {}", table.abandon_policy)?;
+            for phase in TurnPhase::ALL {
+                if let Some(timer) = table.timers.get(*phase) {
+                    writeln!(writer, "timer {} {}", phase, timer.as_secs())?;
+                }
+            }
+            writeln!(writer)?;
+        }
+        Answer::MatchSummary(tablehash, series) => {
+            writeln!(writer, "match {:016X}", tablehash)?;
+            if let Some(games) = series.games {
+                writeln!(writer, "games {}", games)?;
+            }
+            if let Some(score) = series.score {
+                writeln!(writer, "score {}", score)?;
+            }
+            writeln!(writer, "played {}", series.played)?;
+            for (player, count) in series.standings() {
+                writeln!(writer, "player {:016X} {}", player, count)?;
+            }
+            writeln!(writer, "over {}", if series.is_over() { "yes" } else { "no" })?;
+            writeln!(writer)?;
+        }
+        Answer::PlayerStats(stats) => {
+            let total = stats.total();
+            writeln!(writer, "stats {}", stats.name)?;
+            writeln!(writer, "rating {:.0}", stats.rating)?;
+            writeln!(writer, "games {}", total.games)?;
+            writeln!(writer, "durak {}", total.durak)?;
+            writeln!(writer, "winrate {:.3}", total.win_rate())?;
+            writeln!(writer, "place {:.2}", total.average_place())?;
+            writeln!(writer, "abandoned {}", stats.abandoned)?;
+            for (&(ref variant, players), record) in &stats.records {
+                writeln!(
+                    writer,
+                    "record {} {} {} {} {:.3} {:.2}",
+                    variant,
+                    players,
+                    record.games,
+                    record.durak,
+                    record.win_rate(),
+                    record.average_place()
+                )?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::Leaderboard(leaderboard) => {
+            match leaderboard.variant {
+                Some(ref variant) => {
+                    writeln!(writer, "leaderboard {} {}", variant, leaderboard.period)?
+                }
+                None => writeln!(writer, "leaderboard {}", leaderboard.period)?,
+            }
+            for (name, rating) in &leaderboard.rating {
+                writeln!(writer, "rating {} {:.0}", name, rating)?;
+            }
+            for (name, wins) in &leaderboard.wins {
+                writeln!(writer, "won {} {}", name, wins)?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::Error(error) => {
+            writeln!(writer, "ERROR {} {}", error.code(), error)?;
+        }
+        Answer::Chat(sender, message) => {
+            writeln!(writer, "chat {:016X} {}", sender, message)?;
+        }
+        Answer::Renamed(player, name) => {
+            writeln!(writer, "renamed {:016X} {}", player, name)?;
+        }
+        Answer::Ready(players) => {
+            write!(writer, "ready")?;
+            for player in players {
+                write!(writer, " {:016X}", player)?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::Countdown(Some(seconds)) => {
+            writeln!(writer, "countdown {}", seconds)?;
+        }
+        Answer::MoveTimer(seconds, players) => {
+            write!(writer, "timer {}", seconds)?;
+            for player in players {
+                write!(writer, " {:016X}", player)?;
+            }
+            writeln!(writer)?;
+        }
+        Answer::Countdown(None) => {
+            writeln!(writer, "countdown off")?;
+        }
+        Answer::Kicked(tablehash) => {
+            writeln!(writer, "kicked {:016X}", tablehash)?;
+        }
+        Answer::GameState(gamestate) => {
+            write_game_state(writer, id, &gamestate)?;
+            writeln!(writer)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the lines of a game state as seen by player `id`: only the own
+/// hand is shown, everything else is public. The terminating empty line is
+/// left to the caller.
+pub fn write_game_state<W: Write>(
+    writer: &mut W,
+    id: ClientHash,
+    state: &GameState,
+) -> io::Result<()> {
+    if let Some(cards) = state.player_cards.get(&id) {
+        writeln!(
+            writer,
+            "cards {}",
+            cards.iter().fold(String::new(), |acc, x| if acc.is_empty() {
+                format!("{}", x)
+            } else {
+                format!("{} {}", acc, x)
+            })
+        )?;
+    }
+    writeln!(writer, "trump {}", state)?;
+    writeln!(
+        writer,
+        "table {}",
+        state.table_stacks.iter().fold(
+            String::new(),
+            |acc, (x, y)| if acc.is_empty() {
+                match *y {
+                    Some(ref c) => format!("{}/{}", x, c),
+                    None => format!("{}/--", x),
+                }
+            } else {
+                match *y {
+                    Some(ref c) => format!("{} {}/{}", acc, x, c),
+                    None => format!("{} {}/--", acc, x),
+                }
+            },
+        )
+    )?;
+    if let Some(p) = state.target_player {
+        writeln!(writer, "target {:016X}", p)?;
+    }
+    if let Some(p) = state.attack_player {
+        writeln!(writer, "attack {:016X}", p)?;
+    }
+    writeln!(writer, "talon {}", state.talon_size())?;
+    writeln!(
+        writer,
+        "hands {}",
+        state
+            .player_cards
+            .keys()
+            .map(|hash| format!("{:016X}:{}", hash, state.hand_size(hash)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    )?;
+    if !state.finished.is_empty() {
+        writeln!(
+            writer,
+            "out {}",
+            state
+                .finished
+                .iter()
+                .map(|x| format!("{:016X}", x))
+                .collect::<Vec<_>>()
+                .join(" ")
+        )?;
+    }
+    if state.game_over {
+        match state.durak {
+            Some(durak) => writeln!(writer, "durak {:016X}", durak)?,
+            None => writeln!(writer, "durak -")?,
+        }
+    }
+    Ok(())
+}
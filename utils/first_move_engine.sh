@@ -0,0 +1,13 @@
+#!/bin/sh
+# Minimal external engine: always plays the first legal move it was offered.
+# Usage: durakserver --engine first="sh utils/first_move_engine.sh"
+
+first=
+while read -r command rest; do
+    case "$command" in
+        position) first= ;;
+        move) [ -z "$first" ] && first="$rest" ;;
+        go) echo "$first" ;;
+        quit) exit 0 ;;
+    esac
+done
@@ -1,5 +1,6 @@
 #!/usr/bin/env python3
 
+import os
 import re
 import socket
 import random
@@ -8,6 +9,10 @@ import time
 import traceback
 import sys
 
+# Answers sent without being asked for, each a single line.
+NOTIFICATIONS = ['chat', 'renamed', 'ready', 'countdown', 'timer', 'kicked']
+SINGLE_LINES = ['ERROR'] + NOTIFICATIONS
+
 
 class PlayerThread (threading.Thread):
     def __init__(self, name, num):
@@ -15,29 +20,58 @@ class PlayerThread (threading.Thread):
         self.name = name
         self.num = num
 
+    def read_line(self):
+        while '\n' not in self.buffer:
+            try:
+                data = self.socket.recv(4096)
+            except socket.timeout:
+                return None
+            if not data:
+                return None
+            self.buffer += data.decode()
+        line, self.buffer = self.buffer.split('\n', 1)
+        return line
+
+    def read_answer(self):
+        # Errors and notifications are single lines, lists and states are
+        # blocks of lines closed by an empty line.
+        lines = []
+        while True:
+            line = self.read_line()
+            if line is None:
+                if not lines:
+                    return None
+                break
+            if line == '':
+                break
+            lines.append(line)
+            if len(lines) == 1 and line.split()[0] in SINGLE_LINES:
+                break
+        return ''.join(x + '\n' for x in lines)
+
     def send(self, msg, print_all=False, hide_err=False):
         if print_all:
             print('COMMAND %s' % msg)
-        buffer_size = 4096
-        try:
-            self.socket.recv(buffer_size)
-        except socket.timeout:
+        # Drop answers and notifications left over from earlier commands.
+        while self.read_answer() is not None:
             pass
         self.socket.send((msg + '\n').encode())
-        try:
-            answer = self.socket.recv(buffer_size).decode()
-            if print_all or (answer.startswith('ERROR') and not hide_err):
-                print(answer, end='')
-            if answer.startswith('ERROR'):
-                return None
-            return answer
-        except socket.timeout:
+        answer = self.read_answer()
+        while answer and answer.split()[0] in NOTIFICATIONS:
+            answer = self.read_answer()
+        if answer is None:
             return None
+        if print_all or (answer.startswith('ERROR') and not hide_err):
+            print(answer, end='')
+        if answer.startswith('ERROR'):
+            return None
+        return answer
 
     def run(self):
         self.socket = socket.socket()
         self.socket.connect(('localhost', 2342))
         self.socket.settimeout(0.1)
+        self.buffer = ''
 
         try:
             time.sleep(self.num)
@@ -45,11 +79,10 @@ class PlayerThread (threading.Thread):
             data = self.send('table list')
             match = re.search('^([^\s]+) ', data)
             if match is None:
+                # Creating a table seats the creator.
                 self.send('table new Kuchen')
-                data = self.send('table list')
-                match = re.search('^([^\s]+) ', data)
-            tablehash = match.group(1)
-            self.send('table join %s' % tablehash)
+            else:
+                self.send('table join %s' % match.group(1))
             if self.num == 0:
                 time.sleep(3)
                 print('')
@@ -108,7 +141,10 @@ for (num, name) in enumerate(['Kekse', 'Kuchen', 'Quark', 'Ente']):
 for t in threads:
     t.join()
 
+# Shutting down needs the credentials of the [admin] section.
+admin = os.environ.get('DURAK_ADMIN', 'admin')
+password = os.environ.get('DURAK_ADMIN_PASSWORD', 'change me')
 socket = socket.socket()
 socket.connect(('localhost', 2342))
-socket.send(b'quit')
+socket.send(('player login %s %s\nquit\n' % (admin, password)).encode())
 socket.close()